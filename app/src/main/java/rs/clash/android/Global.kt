@@ -2,9 +2,8 @@ package rs.clash.android
 
 import android.util.Log
 import kotlinx.coroutines.flow.MutableStateFlow
+import uniffi.clash_android_ffi.ClashException
 import uniffi.clash_android_ffi.ClashInstance
-import uniffi.clash_android_ffi.EyreException
-import uniffi.clash_android_ffi.formatEyreError
 import android.app.Application as AndroidApplication
 
 class Application : AndroidApplication() {
@@ -28,14 +27,14 @@ private fun setupUncaughtExceptionHandler() {
 
 	Thread.setDefaultUncaughtExceptionHandler { thread, throwable ->
 		try {
-			// Check if the exception is EyreException
-			if (throwable is EyreException) {
-				val errorMessage = formatEyreError(throwable)
-				Log.e("Clash", "Uncaught EyreException on thread ${thread.name}:")
+			// Check if the exception is ClashException
+			if (throwable is ClashException) {
+				val errorMessage = throwable.describe()
+				Log.e("Clash", "Uncaught ClashException on thread ${thread.name}:")
 				Log.e("Clash", errorMessage)
 
 				// Also print to stderr
-				System.err.println("Uncaught EyreException on thread ${thread.name}:")
+				System.err.println("Uncaught ClashException on thread ${thread.name}:")
 				System.err.println(errorMessage)
 			} else {
 				// For other exceptions, log normally
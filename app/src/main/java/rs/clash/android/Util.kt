@@ -6,6 +6,7 @@ import java.util.Locale
 import kotlin.math.log10
 import kotlin.math.pow
 import kotlin.reflect.KClass
+import uniffi.clash_android_ffi.ClashException
 
 fun formatSize(size: Long): String {
 	if (size <= 0) return "0 B"
@@ -19,3 +20,23 @@ val KClass<*>.componentName: ComponentName
 
 val KClass<*>.intent: Intent
 	get() = Intent(Global.application, this.java)
+
+fun ClashException.describe(): String {
+	val context = Global.application
+	return when (this) {
+		is ClashException.ConfigParse -> context.getString(R.string.error_config_parse, reason)
+		is ClashException.Io -> context.getString(R.string.error_io, reason)
+		is ClashException.Network -> context.getString(R.string.error_network, reason)
+		is ClashException.HttpStatus ->
+			if (body.isEmpty()) {
+				context.getString(R.string.error_http_status, code.toInt())
+			} else {
+				context.getString(R.string.error_http_status_body, code.toInt(), body)
+			}
+		is ClashException.StartFailed -> context.getString(R.string.error_start_failed, reason)
+		is ClashException.ControllerUnavailable -> context.getString(R.string.error_controller_unavailable, reason)
+		is ClashException.InvalidOverride -> context.getString(R.string.error_invalid_override, reason)
+		is ClashException.Cancelled -> context.getString(R.string.error_cancelled)
+		is ClashException.Other -> reason
+	}
+}
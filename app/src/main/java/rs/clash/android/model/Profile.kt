@@ -21,6 +21,10 @@ data class Profile(
 	val autoUpdate: Boolean = false,
 	val userAgent: String? = null,
 	val proxyUrl: String? = null,
+	// HTTP validators of the last download, sent back so unchanged
+	// subscriptions are not downloaded again
+	val etag: String? = null,
+	val lastModified: String? = null,
 ) {
 	constructor(jsonObject: JSONObject) : this(
 		id = jsonObject.getString("id"),
@@ -35,6 +39,8 @@ data class Profile(
 		autoUpdate = jsonObject.optBoolean("autoUpdate", false),
 		userAgent = jsonObject.optString("userAgent").takeIf { it.isNotBlank() },
 		proxyUrl = jsonObject.optString("proxyUrl").takeIf { it.isNotBlank() },
+		etag = jsonObject.optString("etag").takeIf { it.isNotBlank() },
+		lastModified = jsonObject.optString("lastModified").takeIf { it.isNotBlank() },
 	)
 
 	fun asJsonObject(): JSONObject {
@@ -51,6 +57,8 @@ data class Profile(
 		jsonObject.put("autoUpdate", this.autoUpdate)
 		if (this.userAgent != null) jsonObject.put("userAgent", this.userAgent)
 		if (this.proxyUrl != null) jsonObject.put("proxyUrl", this.proxyUrl)
+		if (this.etag != null) jsonObject.put("etag", this.etag)
+		if (this.lastModified != null) jsonObject.put("lastModified", this.lastModified)
 		return jsonObject
 	}
 }
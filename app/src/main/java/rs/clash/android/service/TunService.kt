@@ -4,6 +4,10 @@ import android.annotation.SuppressLint
 import android.content.Context
 import android.content.Intent
 import android.content.pm.ServiceInfo
+import android.net.ConnectivityManager
+import android.net.LinkProperties
+import android.net.Network
+import android.net.NetworkCapabilities
 import android.net.VpnService
 import android.os.Build
 import android.os.ParcelFileDescriptor
@@ -13,10 +17,18 @@ import kotlinx.coroutines.Dispatchers
 import kotlinx.coroutines.SupervisorJob
 import kotlinx.coroutines.launch
 import rs.clash.android.Global
+import rs.clash.android.describe
 import rs.clash.android.util.NotificationHelper
 import rs.clash.android.util.PermissionHelper
+import uniffi.clash_android_ffi.ClashException
+import uniffi.clash_android_ffi.LifecycleCallback
+import uniffi.clash_android_ffi.LifecycleEvent
+import uniffi.clash_android_ffi.NetworkInfo
+import uniffi.clash_android_ffi.NetworkTransport
 import uniffi.clash_android_ffi.ProfileOverride
+import uniffi.clash_android_ffi.SystemDns
 import uniffi.clash_android_ffi.runClash
+import uniffi.clash_android_ffi.tunPlan
 import java.io.File
 
 var tunService: TunService? = null
@@ -28,6 +40,78 @@ class TunService : VpnService() {
 	private val serviceScope = CoroutineScope(SupervisorJob() + Dispatchers.Default)
 	private var isDestroying = false
 
+	// The app is excluded from its own VPN, so its default network is the
+	// underlying Wi-Fi or cellular uplink
+	private val networkCallback =
+		object : ConnectivityManager.NetworkCallback() {
+			override fun onCapabilitiesChanged(
+				network: Network,
+				networkCapabilities: NetworkCapabilities,
+			) {
+				val instance = Global.clashInstance ?: return
+				val linkProperties = getSystemService(ConnectivityManager::class.java).getLinkProperties(network)
+				val addresses = linkProperties?.linkAddresses?.map { it.address } ?: emptyList()
+				val transport =
+					when {
+						networkCapabilities.hasTransport(NetworkCapabilities.TRANSPORT_WIFI) -> NetworkTransport.WIFI
+						networkCapabilities.hasTransport(NetworkCapabilities.TRANSPORT_CELLULAR) -> NetworkTransport.CELLULAR
+						networkCapabilities.hasTransport(NetworkCapabilities.TRANSPORT_ETHERNET) -> NetworkTransport.ETHERNET
+						else -> NetworkTransport.OTHER
+					}
+				val info =
+					NetworkInfo(
+						network.networkHandle,
+						transport,
+						ipv4Available = addresses.any { it is java.net.Inet4Address },
+						ipv6Available = addresses.any { it is java.net.Inet6Address && !it.isLinkLocalAddress },
+						metered = !networkCapabilities.hasCapability(NetworkCapabilities.NET_CAPABILITY_NOT_METERED),
+					)
+				serviceScope.launch {
+					try {
+						instance.onNetworkChanged(info)
+					} catch (e: Exception) {
+						Log.e("clash", "Failed to handle network change", e)
+					}
+				}
+			}
+
+			override fun onLinkPropertiesChanged(
+				network: Network,
+				linkProperties: LinkProperties,
+			) {
+				val instance = Global.clashInstance ?: return
+				val dns = systemDns(linkProperties)
+				serviceScope.launch {
+					try {
+						instance.setSystemDns(dns)
+					} catch (e: Exception) {
+						Log.e("clash", "Failed to update system DNS", e)
+					}
+				}
+			}
+		}
+
+	private fun systemDns(linkProperties: LinkProperties): SystemDns =
+		SystemDns(
+			linkProperties.dnsServers.mapNotNull { it.hostAddress },
+			linkProperties.domains
+				?.split(" ", ",")
+				?.filter { it.isNotBlank() }
+				?: emptyList(),
+		)
+
+	private val lifecycleCallback =
+		object : LifecycleCallback {
+			override fun onEvent(event: LifecycleEvent) {
+				Log.i("clash", "clash-rs lifecycle: $event")
+				if (event is LifecycleEvent.CoreExited) {
+					// Don't leave a dead interface capturing all traffic
+					Log.e("clash", "clash-rs exited unexpectedly: ${event.reason}")
+					stopVpn()
+				}
+			}
+		}
+
 	override fun onStartCommand(
 		intent: Intent?,
 		flags: Int,
@@ -71,10 +155,26 @@ class TunService : VpnService() {
 		val prefs = Global.application.getSharedPreferences("settings", Context.MODE_PRIVATE)
 		val builder = Builder()
 		builder.setSession("ClashRS VPNService")
-		builder.addAddress("10.0.0.1", 30)
-		builder.addRoute("0.0.0.0", 0)
-
-		builder.addDnsServer("10.0.0.2")
+		// The fd is filled in once the interface is established
+		val profileOverride =
+			ProfileOverride(
+				-1,
+				fakeIp = prefs.getBoolean("fake_ip", false),
+				ipv6 = prefs.getBoolean("ipv6", true),
+			)
+		val plan =
+			try {
+				tunPlan(profileOverride)
+			} catch (e: ClashException) {
+				Log.e("clash", "Invalid TUN address plan: ${e.describe()}")
+				stopVpn()
+				return
+			}
+		builder.addAddress(plan.addressV4.address, plan.addressV4.prefixLen.toInt())
+		plan.addressV6?.let { builder.addAddress(it.address, it.prefixLen.toInt()) }
+		plan.routes.forEach { builder.addRoute(it.address, it.prefixLen.toInt()) }
+		plan.dnsServers.forEach { builder.addDnsServer(it) }
+		builder.setMtu(plan.mtu.toInt())
 		
 		// Apply app filter settings
 		val appFilterMode = prefs.getString("app_filter_mode", "ALL") ?: "ALL"
@@ -130,17 +230,22 @@ class TunService : VpnService() {
 				}
 		}
 
+		// Start on the uplink's resolvers, so the network callback registered
+		// below does not restart the core to apply them
+		val connectivityManager = getSystemService(ConnectivityManager::class.java)
+		val systemDns = connectivityManager.getLinkProperties(connectivityManager.activeNetwork)?.let(::systemDns)
 		val instance =
 			runClash(
 				Global.profilePath,
 				Global.application.cacheDir.toString(),
-				ProfileOverride(
-					fd,
-					fakeIp = prefs.getBoolean("fake_ip", false),
-					ipv6 = prefs.getBoolean("ipv6", true),
-				),
+				profileOverride.copy(tunFd = fd),
+				lifecycleCallback,
+				systemDns,
 			)
 		Global.clashInstance = instance
+		// Throws if the core fails to bind its listeners or attach the TUN fd
+		instance.waitReady(10_000UL)
+		connectivityManager.registerDefaultNetworkCallback(networkCallback)
 	}
 
 	private fun startForegroundServiceIfNeeded() {
@@ -180,6 +285,11 @@ class TunService : VpnService() {
 			isDestroying = true
 		}
 		Log.i("clash", "Cleaning up VPN service")
+		try {
+			getSystemService(ConnectivityManager::class.java).unregisterNetworkCallback(networkCallback)
+		} catch (e: IllegalArgumentException) {
+			// Not registered yet
+		}
 		// shutdown clash-rs via ClashInstance
 		Global.clashInstance?.shutdown()
 		Global.clashInstance = null
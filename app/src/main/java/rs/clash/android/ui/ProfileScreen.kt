@@ -272,12 +272,15 @@ fun ProfileScreen(
 			dismissButton = {
 				TextButton(
 					onClick = {
-						showUpdateDialog.value = false
-						updateProfile.value = null
-						updateUserAgent.value = ""
-						updateProxyUrl.value = ""
+						if (vm.isDownloading) {
+							vm.cancelDownload()
+						} else {
+							showUpdateDialog.value = false
+							updateProfile.value = null
+							updateUserAgent.value = ""
+							updateProxyUrl.value = ""
+						}
 					},
-					enabled = !vm.isDownloading,
 				) {
 					Text(stringResource(R.string.cancel))
 				}
@@ -426,14 +429,17 @@ fun ProfileScreen(
 			dismissButton = {
 				TextButton(
 					onClick = {
-						showRemoteDialog.value = false
-						remoteName.value = ""
-						remoteUrl.value = ""
-						remoteAutoUpdate.value = false
-						remoteUserAgent.value = ""
-						remoteProxyUrl.value = ""
+						if (vm.isDownloading) {
+							vm.cancelDownload()
+						} else {
+							showRemoteDialog.value = false
+							remoteName.value = ""
+							remoteUrl.value = ""
+							remoteAutoUpdate.value = false
+							remoteUserAgent.value = ""
+							remoteProxyUrl.value = ""
+						}
 					},
-					enabled = !vm.isDownloading,
 				) {
 					Text(stringResource(R.string.cancel))
 				}
@@ -21,14 +21,14 @@ import kotlinx.coroutines.delay
 import kotlinx.coroutines.flow.collectLatest
 import kotlinx.coroutines.launch
 import rs.clash.android.Global
+import rs.clash.android.describe
 import rs.clash.android.service.TunService
 import rs.clash.android.service.tunService
 import rs.clash.android.ui.snackbar.SnackbarController.Companion.showMessage
 import uniffi.clash_android_ffi.ClashController
-import uniffi.clash_android_ffi.EyreException
+import uniffi.clash_android_ffi.ClashException
 import uniffi.clash_android_ffi.MemoryResponse
 import uniffi.clash_android_ffi.Proxy
-import uniffi.clash_android_ffi.formatEyreError
 
 class HomeViewModel : ViewModel() {
 	var profilePath by mutableStateOf<String?>(null)
@@ -115,8 +115,8 @@ class HomeViewModel : ViewModel() {
 			connectionCount = connResponse.connections.size
 			totalDownload = connResponse.downloadTotal
 			totalUpload = connResponse.uploadTotal
-		} catch (e: EyreException) {
-			showMessage("Failed to fetch stats ${formatEyreError(e)}" )
+		} catch (e: ClashException) {
+			showMessage("Failed to fetch stats ${e.describe()}" )
 		}
 	}
 
@@ -135,8 +135,8 @@ class HomeViewModel : ViewModel() {
 					}
 				}
 				this@HomeViewModel.proxies = proxies.toTypedArray()
-			} catch (e: EyreException) {
-				showMessage("API Error: ${formatEyreError(e)}")
+			} catch (e: ClashException) {
+				showMessage("API Error: ${e.describe()}")
 			} finally {
 				isRefreshing = false
 			}
@@ -172,8 +172,8 @@ class HomeViewModel : ViewModel() {
 			try {
 				controller.selectProxy(groupName, proxyName)
 				fetchProxies()
-			} catch (e: EyreException) {
-				showMessage("Failed to select proxy ${e.message}")
+			} catch (e: ClashException) {
+				showMessage("Failed to select proxy ${e.describe()}")
 			}
 		}
 	}
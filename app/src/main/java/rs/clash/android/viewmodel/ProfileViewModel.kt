@@ -17,13 +17,16 @@ import rs.clash.android.ui.snackbar.SnackbarController
 import kotlinx.coroutines.withContext
 import org.json.JSONArray
 import rs.clash.android.Global
+import rs.clash.android.describe
 import rs.clash.android.model.Profile
 import rs.clash.android.model.ProfileType
+import uniffi.clash_android_ffi.DownloadOptions
+import uniffi.clash_android_ffi.DownloadOutcome
 import uniffi.clash_android_ffi.DownloadProgress
 import uniffi.clash_android_ffi.DownloadProgressCallback
-import uniffi.clash_android_ffi.EyreException
-import uniffi.clash_android_ffi.downloadFileWithProgress
-import uniffi.clash_android_ffi.formatEyreError
+import uniffi.clash_android_ffi.DownloadTask
+import uniffi.clash_android_ffi.ClashException
+import uniffi.clash_android_ffi.startDownload
 import uniffi.clash_android_ffi.verifyConfig
 import java.io.File
 import java.io.FileOutputStream
@@ -182,8 +185,8 @@ class ProfileViewModel : ViewModel() {
 
 			SnackbarController.showMessage("配置文件导入成功")
 			file.absolutePath
-		}  catch (e: EyreException) {
-			SnackbarController.showMessage("导入配置失败: ${formatEyreError(e)}")
+		}  catch (e: ClashException) {
+			SnackbarController.showMessage("导入配置失败: ${e.describe()}")
 			null
 		} catch (e: Exception) {
 			val errorMessage = e.message ?: e.toString()
@@ -269,8 +272,8 @@ class ProfileViewModel : ViewModel() {
 	fun verify(path: String): Pair<Boolean, String> =
 		try {
 			true to verifyConfig(path)
-		} catch (e: EyreException) {
-			false to formatEyreError(e)
+		} catch (e: ClashException) {
+			false to e.describe()
 		}
 
 	fun verifyCurrentConfig(context: Context) {
@@ -309,6 +312,13 @@ class ProfileViewModel : ViewModel() {
 	var downloadProgress by mutableStateOf<DownloadProgress?>(null)
 		private set
 
+	@Volatile
+	private var currentDownload: DownloadTask? = null
+
+	fun cancelDownload() {
+		currentDownload?.cancel()
+	}
+
 	fun addRemoteProfile(
 		context: Context,
 		profileName: String,
@@ -345,28 +355,16 @@ class ProfileViewModel : ViewModel() {
 				
 				// Download config from URL using Rust FFI
 				withContext(Dispatchers.IO) {
-					val result =
-						downloadFileWithProgress(
+					val task =
+						startDownload(
 							url,
 							file.absolutePath,
 							userAgent,
 							effectiveProxyUrl,
 							progressCallback,
 						)
-					
-					if (!result.success) {
-						SnackbarController.showMessage(result.errorMessage ?: "未知错误")
-
-						return@withContext
-					}
-					
-					// Verify the downloaded config
-					val (isValid, _) = verify(file.absolutePath)
-					if (!isValid) {
-						file.delete()
-						SnackbarController.showMessage("配置文件验证失败，已删除")
-						return@withContext
-					}
+					currentDownload = task
+					val result = task.awaitResult()
 					
 					withContext(Dispatchers.Main) {
 						// Add to profiles list
@@ -383,6 +381,8 @@ class ProfileViewModel : ViewModel() {
 								autoUpdate = autoUpdate,
 								userAgent = userAgent,
 								proxyUrl = proxyUrl,
+								etag = result.etag,
+								lastModified = result.lastModified,
 							)
 						profiles.add(newProfile)
 						
@@ -401,11 +401,15 @@ class ProfileViewModel : ViewModel() {
 						SnackbarController.showMessage("远程配置添加成功")
 					}
 				}
-			} catch (e: EyreException) {
-				SnackbarController.showMessage("添加远程配置失败: ${formatEyreError(e)}")
+			} catch (e: ClashException.Cancelled) {
+				// The user pressed cancel; the dialog closes once isDownloading drops
+				Log.i("ProfileViewModel", "Download of $profileName cancelled")
+			} catch (e: ClashException) {
+				SnackbarController.showMessage("添加远程配置失败: ${e.describe()}")
 			} catch (e: Exception) {
 				SnackbarController.showMessage("添加远程配置失败: ${e.message ?: e.toString()}")
 			} finally {
+				currentDownload = null
 				isDownloading = false
 				downloadProgress = null
 			}
@@ -447,26 +451,17 @@ class ProfileViewModel : ViewModel() {
 							}
 						}
 					
-					val result =
-						downloadFileWithProgress(
+					val task =
+						startDownload(
 							profile.url,
 							file.absolutePath,
 							effectiveUserAgent,
 							effectiveProxyUrl,
 							progressCallback,
+							DownloadOptions(etag = profile.etag, lastModified = profile.lastModified),
 						)
-					
-					if (!result.success) {
-						SnackbarController.showMessage("更新配置失败: ${result.errorMessage ?: "未知错误"}")
-						return@withContext
-					}
-					
-					// Verify the downloaded config
-					val (isValid, error) = verify(file.absolutePath)
-					if (!isValid) {
-						SnackbarController.showMessage("配置文件验证失败: $error")
-						return@withContext
-					}
+					currentDownload = task
+					val result = task.awaitResult()
 					
 					withContext(Dispatchers.Main) {
 						// Update profile
@@ -476,6 +471,8 @@ class ProfileViewModel : ViewModel() {
 								profiles[index].copy(
 									fileSize = result.fileSize.toLong(),
 									lastUpdated = System.currentTimeMillis(),
+									etag = result.etag,
+									lastModified = result.lastModified,
 								)
 							if (profiles[index].isActive) {
 								activeProfile = profiles[index]
@@ -483,14 +480,22 @@ class ProfileViewModel : ViewModel() {
 							saveProfiles()
 						}
 						
-						SnackbarController.showMessage("配置更新成功")
+						if (result.outcome == DownloadOutcome.NOT_MODIFIED) {
+							SnackbarController.showMessage("配置已是最新")
+						} else {
+							SnackbarController.showMessage("配置更新成功")
+						}
 					}
 				}
-			} catch (e: EyreException) {
-			SnackbarController.showMessage("添加远程配置失败: ${formatEyreError(e)}")
-		} catch (e: Exception) {
-			SnackbarController.showMessage("更新配置失败: ${e.message ?: e.toString()}")
+			} catch (e: ClashException.Cancelled) {
+				// The user pressed cancel; the dialog closes once isDownloading drops
+				Log.i("ProfileViewModel", "Update of ${profile.name} cancelled")
+			} catch (e: ClashException) {
+				SnackbarController.showMessage("更新配置失败: ${e.describe()}")
+			} catch (e: Exception) {
+				SnackbarController.showMessage("更新配置失败: ${e.message ?: e.toString()}")
 			} finally {
+				currentDownload = null
 				isDownloading = false
 				downloadProgress = null
 			}
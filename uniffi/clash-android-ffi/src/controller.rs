@@ -1,6 +1,5 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
-use eyre::Context;
 #[cfg(unix)]
 use http_body_util::{BodyExt, Full};
 #[cfg(unix)]
@@ -10,13 +9,20 @@ use hyper::body::Bytes;
 #[cfg(unix)]
 use hyper_util::client::legacy::Client;
 #[cfg(unix)]
-use hyper_util::rt::TokioExecutor;
+use hyper_util::rt::{TokioExecutor, TokioTimer};
 #[cfg(unix)]
 use hyperlocal::{UnixConnector, Uri as UnixUri};
 use serde::{Deserialize, Serialize};
+use tokio::{sync::Semaphore, task::JoinSet};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
 use urlencoding::encode;
 
-use crate::EyreError;
+use crate::error::ClashError;
+
+/// The core answers a provider health check only after every member has been
+/// tested, so large providers need far longer than a plain request
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(60);
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, uniffi::Enum)]
 #[serde(rename_all = "lowercase")]
@@ -55,6 +61,22 @@ pub struct DelayResponse {
     pub delay: i32,
 }
 
+/// Outcome of testing one member of a group in
+/// [`ClashController::test_group_delay`]
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ProxyDelay {
+    pub name: String,
+    /// Round-trip delay in milliseconds, `None` if the test failed
+    pub delay: Option<i32>,
+    pub error: Option<String>,
+}
+
+#[uniffi::export(callback_interface)]
+pub trait DelayTestCallback: Send + Sync {
+    /// Called as soon as each proxy finishes, in completion order
+    fn on_result(&self, result: ProxyDelay);
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, uniffi::Record)]
 pub struct MemoryResponse {
     pub inuse: i64,
@@ -95,6 +117,104 @@ pub struct ConnectionsResponse {
     pub connections: Vec<Connection>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, uniffi::Record)]
+pub struct ProxyProvider {
+    pub name: String,
+    /// How the provider is loaded (e.g., HTTP, File)
+    #[serde(rename = "vehicleType")]
+    pub vehicle_type: String,
+    /// Time of the last successful update
+    #[serde(rename = "updatedAt", default)]
+    pub updated_at: Option<String>,
+    /// Proxies supplied by the provider, with their delay history
+    #[serde(default)]
+    pub proxies: Vec<Proxy>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ProxyProvidersResponse {
+    pub providers: HashMap<String, ProxyProvider>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, uniffi::Record)]
+pub struct RuleProvider {
+    pub name: String,
+    /// How the provider is loaded (e.g., HTTP, File)
+    #[serde(rename = "vehicleType")]
+    pub vehicle_type: String,
+    /// Rule set behavior (domain, ipcidr or classical)
+    pub behavior: String,
+    /// Rule set file format (e.g., yaml, text, mrs)
+    #[serde(default)]
+    pub format: Option<String>,
+    #[serde(rename = "ruleCount", default)]
+    pub rule_count: i64,
+    /// Time of the last successful update
+    #[serde(rename = "updatedAt", default)]
+    pub updated_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RuleProvidersResponse {
+    pub providers: HashMap<String, RuleProvider>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, uniffi::Record)]
+pub struct Rule {
+    /// Rule type (e.g., DomainSuffix, IpCidr, RuleSet, Match)
+    #[serde(rename = "type")]
+    pub rule_type: String,
+    pub payload: String,
+    /// Proxy or group the rule routes to
+    pub proxy: String,
+    /// Rule provider backing the rule (only for RULE-SET rules)
+    #[serde(default)]
+    pub provider: Option<String>,
+}
+
+impl Rule {
+    fn is_rule_set(&self) -> bool {
+        self.rule_type
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_lowercase())
+            .eq("ruleset".chars())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RulesResponse {
+    pub rules: Vec<Rule>,
+}
+
+/// Criteria for [`ClashController::close_connections_matching`]. Every field
+/// that is set must match; unset fields match anything.
+#[derive(Debug, Clone, Default, uniffi::Record)]
+pub struct ConnectionFilter {
+    /// Matches the sniffed host or the destination IP
+    #[uniffi(default = None)]
+    pub host: Option<String>,
+    /// Matches the rule that routed the connection, e.g. `DomainSuffix`
+    #[uniffi(default = None)]
+    pub rule: Option<String>,
+    /// Matches if any proxy or group in the chain has this name
+    #[uniffi(default = None)]
+    pub chain: Option<String>,
+}
+
+impl ConnectionFilter {
+    fn matches(&self, conn: &Connection) -> bool {
+        self.host
+            .as_ref()
+            .is_none_or(|host| conn.metadata.host == *host || conn.metadata.destination_ip == *host)
+            && self.rule.as_ref().is_none_or(|rule| conn.rule == *rule)
+            && self
+                .chain
+                .as_ref()
+                .is_none_or(|chain| conn.chains.contains(chain))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, uniffi::Record)]
 pub struct ConfigResponse {
     #[serde(rename = "external-controller")]
@@ -103,16 +223,100 @@ pub struct ConfigResponse {
     pub mode: Option<Mode>,
 }
 
+/// A single sample from the `/traffic` stream
+#[derive(Debug, Clone, Serialize, Deserialize, uniffi::Record)]
+pub struct Traffic {
+    /// Upload speed in bytes per second
+    pub up: i64,
+    /// Download speed in bytes per second
+    pub down: i64,
+}
+
+#[uniffi::export(callback_interface)]
+pub trait TrafficCallback: Send + Sync {
+    fn on_traffic(&self, traffic: Traffic);
+    /// Called once when the stream ends for any reason other than
+    /// [`Subscription::cancel`]
+    fn on_error(&self, message: String);
+}
+
+/// Handle to a streaming controller endpoint. The stream stops when
+/// `cancel` is called or the handle is dropped.
+#[derive(uniffi::Object)]
+pub struct Subscription {
+    cancel_token: CancellationToken,
+}
+
+impl Subscription {
+    pub(crate) fn new() -> Arc<Self> {
+        Arc::new(Self {
+            cancel_token: CancellationToken::new(),
+        })
+    }
+
+    pub(crate) fn token(&self) -> CancellationToken {
+        self.cancel_token.clone()
+    }
+}
+
+#[uniffi::export]
+impl Subscription {
+    /// Stop the stream and close the underlying socket
+    pub fn cancel(&self) {
+        self.cancel_token.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel_token.is_cancelled()
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.cancel_token.cancel();
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct ProxiesResponse {
     pub proxies: HashMap<String, Proxy>,
 }
 
-/// Clash HTTP API client using Unix domain socket
+/// Tuning for the pooled connection [`ClashController`] keeps to the socket
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ControllerOptions {
+    /// Deadline for a whole request/response exchange, in milliseconds.
+    /// Delay tests are extended to cover their own test timeout and provider
+    /// health checks to at least a minute.
+    #[uniffi(default = 10000)]
+    pub request_timeout_ms: u64,
+    /// How long an idle keep-alive connection stays open, in milliseconds
+    #[uniffi(default = 90000)]
+    pub pool_idle_timeout_ms: u64,
+    /// Maximum number of idle keep-alive connections
+    #[uniffi(default = 4)]
+    pub pool_max_idle: u32,
+}
+
+impl Default for ControllerOptions {
+    fn default() -> Self {
+        Self {
+            request_timeout_ms: 10000,
+            pool_idle_timeout_ms: 90000,
+            pool_max_idle: 4,
+        }
+    }
+}
+
+/// Clash HTTP API client using Unix domain socket. A single instance keeps
+/// a keep-alive connection pool and is safe to share between coroutines.
 #[derive(uniffi::Object)]
 pub struct ClashController {
     #[allow(dead_code)]
     socket_path: String,
+    request_timeout: Duration,
+    #[cfg(unix)]
+    client: Client<UnixConnector, Full<Bytes>>,
 }
 
 #[uniffi::export(async_runtime = "tokio")]
@@ -120,11 +324,29 @@ impl ClashController {
     /// Create a new HTTP client that connects via Unix domain socket
     #[uniffi::constructor]
     pub fn new(socket_path: String) -> Arc<Self> {
-        Arc::new(Self { socket_path })
+        Self::with_options(socket_path, ControllerOptions::default())
+    }
+
+    /// Create a new HTTP client with custom timeouts and pool limits
+    #[uniffi::constructor]
+    pub fn with_options(socket_path: String, options: ControllerOptions) -> Arc<Self> {
+        #[cfg(unix)]
+        let client = Client::builder(TokioExecutor::new())
+            .pool_timer(TokioTimer::new())
+            .pool_idle_timeout(Duration::from_millis(options.pool_idle_timeout_ms))
+            .pool_max_idle_per_host(options.pool_max_idle as usize)
+            .build(UnixConnector);
+
+        Arc::new(Self {
+            socket_path,
+            request_timeout: Duration::from_millis(options.request_timeout_ms),
+            #[cfg(unix)]
+            client,
+        })
     }
 
     /// Get all proxies
-    pub async fn get_proxies(&self) -> Result<Vec<Proxy>, EyreError> {
+    pub async fn get_proxies(&self) -> Result<Vec<Proxy>, ClashError> {
         let mode = self.get_mode().await?.unwrap_or(Mode::Rule);
 
         // If in direct mode, return a single DIRECT proxy
@@ -175,7 +397,7 @@ impl ClashController {
         &self,
         group_name: String,
         proxy_name: String,
-    ) -> Result<(), EyreError> {
+    ) -> Result<(), ClashError> {
         let body = serde_json::json!(
             {
                 "name": proxy_name
@@ -193,7 +415,7 @@ impl ClashController {
         name: String,
         url: Option<String>,
         timeout: Option<i32>,
-    ) -> Result<DelayResponse, EyreError> {
+    ) -> Result<DelayResponse, ClashError> {
         let test_url = url.unwrap_or_else(|| "http://www.gstatic.com/generate_204".to_string());
         let timeout_ms = timeout.unwrap_or(5000);
 
@@ -203,34 +425,238 @@ impl ClashController {
             encode(&test_url),
             timeout_ms
         );
-        self.request("GET", &path, None).await
+        // The core only answers once its own test finishes, so leave a margin
+        // on top of the test timeout.
+        let request_timeout = self
+            .request_timeout
+            .max(Duration::from_millis(timeout_ms.max(0) as u64 + 1000));
+        self.request_with_timeout("GET", &path, None, request_timeout)
+            .await
+    }
+
+    /// Test every member of a proxy group with at most `max_concurrency`
+    /// requests in flight. Each result is reported through `callback` as it
+    /// finishes; the returned summary is sorted by delay with failures last.
+    /// Fails with `InvalidOverride` if `group_name` is not a proxy group.
+    pub async fn test_group_delay(
+        self: Arc<Self>,
+        group_name: String,
+        url: Option<String>,
+        timeout: Option<i32>,
+        max_concurrency: u32,
+        callback: Option<Box<dyn DelayTestCallback>>,
+    ) -> Result<Vec<ProxyDelay>, ClashError> {
+        let path = format!("/proxies/{}", encode(&group_name));
+        let group: Proxy = self.request("GET", &path, None).await?;
+        if group.all.is_empty() {
+            return Err(ClashError::invalid_override(format!(
+                "{group_name} is not a proxy group"
+            )));
+        }
+
+        let semaphore = Arc::new(Semaphore::new(max_concurrency.max(1) as usize));
+        let mut tasks = JoinSet::new();
+        for name in group.all {
+            let controller = self.clone();
+            let semaphore = semaphore.clone();
+            let url = url.clone();
+            tasks.spawn(async move {
+                let _permit = semaphore.acquire_owned().await;
+                match controller.get_proxy_delay(name.clone(), url, timeout).await {
+                    Ok(response) if response.delay > 0 => ProxyDelay {
+                        name,
+                        delay: Some(response.delay),
+                        error: None,
+                    },
+                    Ok(_) => ProxyDelay {
+                        name,
+                        delay: None,
+                        error: Some("timeout".to_string()),
+                    },
+                    Err(err) => ProxyDelay {
+                        name,
+                        delay: None,
+                        error: Some(err.to_string()),
+                    },
+                }
+            });
+        }
+
+        let mut results = Vec::with_capacity(tasks.len());
+        while let Some(result) = tasks.join_next().await {
+            let result = result.map_err(ClashError::other)?;
+            if let Some(ref callback) = callback {
+                callback.on_result(result.clone());
+            }
+            results.push(result);
+        }
+        results.sort_by_key(|r| (r.delay.is_none(), r.delay));
+        Ok(results)
     }
 
     /// Get memory statistics.
-    pub async fn get_memory(&self) -> Result<MemoryResponse, EyreError> {
+    pub async fn get_memory(&self) -> Result<MemoryResponse, ClashError> {
         self.request("GET", "/memory", None).await
     }
 
     /// Get active connections
-    pub async fn get_connections(&self) -> Result<ConnectionsResponse, EyreError> {
+    pub async fn get_connections(&self) -> Result<ConnectionsResponse, ClashError> {
         self.request("GET", "/connections", None).await
     }
 
+    /// Get the proxy providers declared in the profile. The implicit
+    /// `Compatible` providers that wrap inline proxies are left out.
+    pub async fn get_proxy_providers(&self) -> Result<Vec<ProxyProvider>, ClashError> {
+        let response: ProxyProvidersResponse =
+            self.request("GET", "/providers/proxies", None).await?;
+        let mut providers: Vec<_> = response
+            .providers
+            .into_values()
+            .filter(|p| p.vehicle_type != "Compatible")
+            .collect();
+        providers.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(providers)
+    }
+
+    /// Re-fetch a proxy provider from its source
+    pub async fn update_proxy_provider(&self, name: String) -> Result<(), ClashError> {
+        let path = format!("/providers/proxies/{}", encode(&name));
+        self.request_no_response("PUT", &path, None).await
+    }
+
+    /// Run a health check against every proxy of a provider
+    pub async fn health_check_proxy_provider(&self, name: String) -> Result<(), ClashError> {
+        let path = format!("/providers/proxies/{}/healthcheck", encode(&name));
+        let timeout = self.request_timeout.max(HEALTH_CHECK_TIMEOUT);
+        self.do_request("GET", &path, None, timeout)
+            .await
+            .map(|_| ())
+    }
+
+    /// Run a health check on every proxy provider, including the implicit
+    /// ones behind proxy groups, so url-test and fallback groups re-pick
+    /// their best member. Failures of individual providers are only logged.
+    pub async fn health_check_all(self: Arc<Self>) -> Result<(), ClashError> {
+        let response: ProxyProvidersResponse =
+            self.request("GET", "/providers/proxies", None).await?;
+        let mut tasks = JoinSet::new();
+        for name in response.providers.into_keys() {
+            let controller = self.clone();
+            tasks.spawn(async move {
+                if let Err(err) = controller.health_check_proxy_provider(name.clone()).await {
+                    warn!("Health check of provider {name} failed: {err}");
+                }
+            });
+        }
+        while tasks.join_next().await.is_some() {}
+        Ok(())
+    }
+
+    /// Get the rule providers declared in the profile
+    pub async fn get_rule_providers(&self) -> Result<Vec<RuleProvider>, ClashError> {
+        let response: RuleProvidersResponse = self.request("GET", "/providers/rules", None).await?;
+        let mut providers: Vec<_> = response.providers.into_values().collect();
+        providers.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(providers)
+    }
+
+    /// Re-fetch a rule provider from its source
+    pub async fn update_rule_provider(&self, name: String) -> Result<(), ClashError> {
+        let path = format!("/providers/rules/{}", encode(&name));
+        self.request_no_response("PUT", &path, None).await
+    }
+
+    /// Get the loaded rules in evaluation order
+    pub async fn get_rules(&self) -> Result<Vec<Rule>, ClashError> {
+        let response: RulesResponse = self.request("GET", "/rules", None).await?;
+        Ok(response
+            .rules
+            .into_iter()
+            .map(|mut rule| {
+                if rule.provider.is_none() && rule.is_rule_set() {
+                    rule.provider = Some(rule.payload.clone());
+                }
+                rule
+            })
+            .collect())
+    }
+
+    /// Close a single connection by id
+    pub async fn close_connection(&self, id: String) -> Result<(), ClashError> {
+        let path = format!("/connections/{}", encode(&id));
+        self.request_no_response("DELETE", &path, None).await
+    }
+
+    /// Close every active connection
+    pub async fn close_all_connections(&self) -> Result<(), ClashError> {
+        self.request_no_response("DELETE", "/connections", None)
+            .await
+    }
+
+    /// Drop every cached DNS answer
+    pub async fn flush_dns_cache(&self) -> Result<(), ClashError> {
+        self.request_no_response("POST", "/cache/dns/flush", None)
+            .await
+    }
+
+    /// Close every connection matching `filter`, returning how many were
+    /// closed
+    pub async fn close_connections_matching(
+        &self,
+        filter: ConnectionFilter,
+    ) -> Result<u32, ClashError> {
+        let response = self.get_connections().await?;
+        let mut closed = 0;
+        for conn in response.connections.iter().filter(|c| filter.matches(c)) {
+            match self.close_connection(conn.id.clone()).await {
+                Ok(()) => closed += 1,
+                Err(err) => warn!("Failed to close connection {}: {err}", conn.id),
+            }
+        }
+        Ok(closed)
+    }
+
+    /// Subscribe to real-time upload/download speed. The core pushes one
+    /// sample per second until the subscription is cancelled.
+    pub async fn subscribe_traffic(
+        self: Arc<Self>,
+        callback: Box<dyn TrafficCallback>,
+    ) -> Arc<Subscription> {
+        let subscription = Subscription::new();
+        let token = subscription.token();
+        tokio::spawn(async move {
+            let result = self
+                .stream("/traffic", token.clone(), |traffic: Traffic| {
+                    callback.on_traffic(traffic)
+                })
+                .await;
+            match result {
+                Ok(()) => info!("traffic subscription cancelled"),
+                Err(err) if !token.is_cancelled() => {
+                    warn!("traffic subscription ended: {err}");
+                    callback.on_error(err.to_string());
+                }
+                Err(_) => {}
+            }
+        });
+        subscription
+    }
+
     /// Get current configuration
-    pub async fn get_configs(&self) -> Result<ConfigResponse, EyreError> {
+    pub async fn get_configs(&self) -> Result<ConfigResponse, ClashError> {
         self.request("GET", "/configs", None).await
     }
 
     /// Update configuration
-    pub async fn update_config(&self, config: HashMap<String, String>) -> Result<(), EyreError> {
-        let body_bytes = serde_json::to_vec(&config).wrap_err("Failed to serialize config")?;
+    pub async fn update_config(&self, config: HashMap<String, String>) -> Result<(), ClashError> {
+        let body_bytes = serde_json::to_vec(&config)?;
 
         self.request_no_response("PATCH", "/configs", Some(body_bytes))
             .await
     }
 
     /// Set proxy mode (rule, global, direct)
-    pub async fn set_mode(&self, mode: Mode) -> Result<(), EyreError> {
+    pub async fn set_mode(&self, mode: Mode) -> Result<(), ClashError> {
         let mode_str = match mode {
             Mode::Rule => "rule",
             Mode::Global => "global",
@@ -242,21 +668,65 @@ impl ClashController {
     }
 
     /// Get current proxy mode
-    pub async fn get_mode(&self) -> Result<Option<Mode>, EyreError> {
+    pub async fn get_mode(&self) -> Result<Option<Mode>, ClashError> {
         let config = self.get_configs().await?;
         Ok(config.mode)
     }
 }
 
+impl ClashController {
+    async fn request_no_response(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<Vec<u8>>,
+    ) -> Result<(), ClashError> {
+        self.do_request(method, path, body, self.request_timeout)
+            .await
+            .map(|_| ())
+    }
+
+    async fn request<T>(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<Vec<u8>>,
+    ) -> Result<T, ClashError>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        self.request_with_timeout(method, path, body, self.request_timeout)
+            .await
+    }
+
+    async fn request_with_timeout<T>(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<Vec<u8>>,
+        timeout: Duration,
+    ) -> Result<T, ClashError>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let body_bytes = self.do_request(method, path, body, timeout).await?;
+        serde_json::from_slice(&body_bytes).map_err(|e| {
+            ClashError::other(format!(
+                "Failed to parse JSON response: {e}: {}",
+                String::from_utf8_lossy(&body_bytes)
+            ))
+        })
+    }
+}
+
 #[cfg(unix)]
 impl ClashController {
-    async fn do_request(
+    async fn send(
         &self,
         method: &str,
         path: &str,
         body: Option<Vec<u8>>,
-    ) -> Result<hyper::body::Bytes, EyreError> {
-        let client = Client::builder(TokioExecutor::new()).build(UnixConnector);
+    ) -> Result<hyper::Response<hyper::body::Incoming>, ClashError> {
         let uri: hyper::Uri = UnixUri::new(&self.socket_path, path).into();
 
         let request_builder = Request::builder()
@@ -267,55 +737,114 @@ impl ClashController {
         let request = if let Some(body_data) = body {
             request_builder
                 .body(Full::new(Bytes::from(body_data)))
-                .wrap_err("Failed to build request with body")?
+                .map_err(ClashError::other)?
         } else {
             request_builder
                 .body(Full::new(Bytes::new()))
-                .wrap_err("Failed to build request")?
+                .map_err(ClashError::other)?
         };
 
-        let response = client
+        let response = self
+            .client
             .request(request)
             .await
-            .wrap_err("HTTP request failed")?;
-
-        if !response.status().is_success() {
-            return Err(eyre::eyre!("HTTP status error: {}", response.status()));
+            .map_err(ClashError::controller_unavailable)?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = match response.into_body().collect().await {
+                Ok(body) => String::from_utf8_lossy(&body.to_bytes()).into_owned(),
+                Err(_) => String::new(),
+            };
+            return Err(ClashError::HttpStatus {
+                code: status.as_u16(),
+                body,
+            });
         }
 
-        response
-            .into_body()
-            .collect()
-            .await
-            .wrap_err("Failed to read response body")
-            .map(|b| b.to_bytes())
+        Ok(response)
     }
 
-    async fn request_no_response(
+    async fn do_request(
         &self,
         method: &str,
         path: &str,
         body: Option<Vec<u8>>,
-    ) -> Result<(), EyreError> {
-        self.do_request(method, path, body).await.map(|_| ())
+        timeout: Duration,
+    ) -> Result<hyper::body::Bytes, ClashError> {
+        let exchange = async {
+            self.send(method, path, body)
+                .await?
+                .into_body()
+                .collect()
+                .await
+                .map_err(ClashError::network)
+                .map(|b| b.to_bytes())
+        };
+        tokio::time::timeout(timeout, exchange).await.map_err(|_| {
+            ClashError::controller_unavailable(format!(
+                "{method} {path} timed out after {} ms",
+                timeout.as_millis()
+            ))
+        })?
     }
 
-    async fn request<T>(
+    /// Read a newline-delimited JSON stream, handing each item to `on_item`
+    /// until the token is cancelled or the controller closes the stream.
+    async fn stream<T, F>(
         &self,
-        method: &str,
         path: &str,
-        body: Option<Vec<u8>>,
-    ) -> Result<T, EyreError>
+        token: CancellationToken,
+        mut on_item: F,
+    ) -> Result<(), ClashError>
     where
         T: serde::de::DeserializeOwned,
+        F: FnMut(T),
     {
-        let body_bytes = self.do_request(method, path, body).await?;
-        serde_json::from_slice(&body_bytes).wrap_err_with(|| {
-            format!(
-                "Failed to parse JSON response: {}",
-                String::from_utf8_lossy(&body_bytes)
-            )
-        })
+        let response = tokio::time::timeout(self.request_timeout, self.send("GET", path, None))
+            .await
+            .map_err(|_| {
+                ClashError::controller_unavailable(format!(
+                    "GET {path} timed out waiting for the stream to open"
+                ))
+            })??;
+        let mut body = response.into_body();
+        let mut buffer = Vec::new();
+
+        loop {
+            let frame = tokio::select! {
+                _ = token.cancelled() => return Ok(()),
+                frame = body.frame() => frame,
+            };
+            let Some(frame) = frame else {
+                return Err(ClashError::controller_unavailable(format!(
+                    "Stream {path} closed by controller"
+                )));
+            };
+            let frame = frame.map_err(ClashError::network)?;
+            let Ok(data) = frame.into_data() else {
+                continue;
+            };
+            buffer.extend_from_slice(&data);
+
+            while let Some(pos) = buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=pos).collect();
+                let line = line.trim_ascii();
+                if line.is_empty() {
+                    continue;
+                }
+                let item = serde_json::from_slice(line).map_err(|e| {
+                    ClashError::other(format!(
+                        "Failed to parse stream item: {e}: {}",
+                        String::from_utf8_lossy(line)
+                    ))
+                })?;
+                if token.is_cancelled() {
+                    return Ok(());
+                }
+                on_item(item);
+            }
+        }
     }
 }
 
@@ -326,36 +855,25 @@ impl ClashController {
         _method: &str,
         _path: &str,
         _body: Option<Vec<u8>>,
-    ) -> Result<hyper::body::Bytes, EyreError> {
-        Err(eyre::eyre!(
-            "ClashController requires Unix domain sockets, which are not available on this platform"
+        _timeout: Duration,
+    ) -> Result<hyper::body::Bytes, ClashError> {
+        Err(ClashError::controller_unavailable(
+            "ClashController requires Unix domain sockets, which are not available on this platform",
         ))
     }
 
-    async fn request_no_response(
-        &self,
-        method: &str,
-        path: &str,
-        body: Option<Vec<u8>>,
-    ) -> Result<(), EyreError> {
-        self.do_request(method, path, body).await.map(|_| ())
-    }
-
-    async fn request<T>(
+    async fn stream<T, F>(
         &self,
-        method: &str,
         path: &str,
-        body: Option<Vec<u8>>,
-    ) -> Result<T, EyreError>
+        _token: CancellationToken,
+        _on_item: F,
+    ) -> Result<(), ClashError>
     where
         T: serde::de::DeserializeOwned,
+        F: FnMut(T),
     {
-        let body_bytes = self.do_request(method, path, body).await?;
-        serde_json::from_slice(&body_bytes).wrap_err_with(|| {
-            format!(
-                "Failed to parse JSON response: {}",
-                String::from_utf8_lossy(&body_bytes)
-            )
-        })
+        self.do_request("GET", path, None, self.request_timeout)
+            .await
+            .map(|_| ())
     }
 }
@@ -0,0 +1,108 @@
+use std::fmt;
+
+/// Error returned across the FFI boundary. Each variant carries enough
+/// structure for the app to pick a localized message and react per category.
+#[derive(Debug, Clone, uniffi::Error)]
+pub enum ClashError {
+    /// The profile could not be read or parsed
+    ConfigParse { reason: String },
+    /// A local file or directory operation failed
+    Io { reason: String },
+    /// A remote request failed before a response was received
+    Network { reason: String },
+    /// The server answered with a non-success status
+    HttpStatus { code: u16, body: String },
+    /// The core exited or never became ready after `run_clash`
+    StartFailed { reason: String },
+    /// The controller socket is missing, refused the connection or timed out
+    ControllerUnavailable { reason: String },
+    /// A value in `ProfileOverride` or another argument is invalid
+    InvalidOverride { reason: String },
+    /// The operation was cancelled by the caller
+    Cancelled,
+    /// Anything that does not fit the categories above
+    Other { reason: String },
+}
+
+impl ClashError {
+    pub(crate) fn config_parse(err: impl fmt::Display) -> Self {
+        Self::ConfigParse {
+            reason: err.to_string(),
+        }
+    }
+
+    pub(crate) fn io(err: impl fmt::Display) -> Self {
+        Self::Io {
+            reason: err.to_string(),
+        }
+    }
+
+    pub(crate) fn network(err: impl fmt::Display) -> Self {
+        Self::Network {
+            reason: err.to_string(),
+        }
+    }
+
+    pub(crate) fn controller_unavailable(err: impl fmt::Display) -> Self {
+        Self::ControllerUnavailable {
+            reason: err.to_string(),
+        }
+    }
+
+    pub(crate) fn invalid_override(err: impl fmt::Display) -> Self {
+        Self::InvalidOverride {
+            reason: err.to_string(),
+        }
+    }
+
+    pub(crate) fn other(err: impl fmt::Display) -> Self {
+        Self::Other {
+            reason: err.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ClashError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ConfigParse { reason } => write!(f, "Config parse error: {reason}"),
+            Self::Io { reason } => write!(f, "I/O error: {reason}"),
+            Self::Network { reason } => write!(f, "Network error: {reason}"),
+            Self::HttpStatus { code, body } if body.is_empty() => write!(f, "HTTP {code}"),
+            Self::HttpStatus { code, body } => write!(f, "HTTP {code}: {body}"),
+            Self::StartFailed { reason } => write!(f, "Core failed to start: {reason}"),
+            Self::ControllerUnavailable { reason } => {
+                write!(f, "Controller unavailable: {reason}")
+            }
+            Self::InvalidOverride { reason } => write!(f, "Invalid override: {reason}"),
+            Self::Cancelled => write!(f, "Cancelled"),
+            Self::Other { reason } => write!(f, "{reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ClashError {}
+
+impl From<eyre::Report> for ClashError {
+    fn from(err: eyre::Report) -> Self {
+        match err.downcast::<ClashError>() {
+            Ok(err) => err,
+            Err(err) if err.downcast_ref::<std::io::Error>().is_some() => {
+                Self::io(format!("{err:#}"))
+            }
+            Err(err) => Self::other(format!("{err:#}")),
+        }
+    }
+}
+
+impl From<std::io::Error> for ClashError {
+    fn from(err: std::io::Error) -> Self {
+        Self::io(err)
+    }
+}
+
+impl From<serde_json::Error> for ClashError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::other(err)
+    }
+}
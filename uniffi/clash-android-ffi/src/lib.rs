@@ -6,45 +6,54 @@ static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
 #[global_allocator]
 static GLOBAL: ::mimalloc::MiMalloc = ::mimalloc::MiMalloc;
 
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::path::PathBuf;
-use std::sync::{Arc, Once};
+use std::sync::{
+    Arc, Once,
+    atomic::{AtomicU16, Ordering},
+};
+use std::time::Duration;
 
 use async_compat::set_runtime_builder;
 use clash_lib::{
     Config,
-    app::{
-        dns,
-        dns::config::{DNSListenAddr, DNSNetMode, NameServer},
-    },
+    app::{dns, dns::config::DNSListenAddr},
     config::{
         config::Controller,
         def::{Config as ConfigDef, DNSMode, LogLevel, Port},
-        internal::config::TunConfig,
+        internal::config::{Config as InternalConfig, TunConfig},
     },
     shutdown as clash_shutdown, start,
 };
-use log::init_logger;
+use controller::{ClashController, Subscription};
+use error::ClashError;
+use log::{LogCallback, init_logger};
+use nameserver::{DnsPreset, SystemDns, udp_bootstrap};
 use once_cell::sync::OnceCell;
-use tokio::task::JoinHandle;
+use route::RouteExclusions;
+use tokio::{
+    net::TcpStream,
+    sync::{Mutex, broadcast::error::RecvError, watch},
+    task::JoinHandle,
+};
 use tokio_util::sync::CancellationToken;
-use tracing::{error, info};
+use tracing::{error, info, warn};
+use tun::TunPlan;
 use url::Host;
 
 pub mod controller;
+pub mod error;
 pub mod log;
+pub mod nameserver;
+pub mod route;
+pub mod subscription;
+pub mod tun;
 pub mod util;
 
-type EyreError = eyre::Error;
-#[uniffi::remote(Object)]
-pub struct EyreError;
-
-#[uniffi::export]
-pub fn format_eyre_error(err: &EyreError) -> String {
-    err.to_string()
-}
+/// How long `on_network_changed` waits for a core that is still starting
+const READY_TIMEOUT_MS: u64 = 10_000;
 
-#[derive(uniffi::Record)]
+#[derive(Clone, uniffi::Record)]
 pub struct ProfileOverride {
     pub tun_fd: i32,
 
@@ -65,28 +74,377 @@ pub struct ProfileOverride {
 
     #[uniffi(default = true)]
     pub ipv6: bool,
+    /// IPv4 address and prefix of the TUN interface. The DNS server takes
+    /// the next address, so the prefix must leave room for it.
+    #[uniffi(default = "172.19.0.1/30")]
+    pub tun_gateway: String,
+    /// IPv6 address and prefix of the TUN interface. Only used when `ipv6`
+    /// is set.
+    #[uniffi(default = "fdfe:dcba:9876::1/126")]
+    pub tun_gateway_v6: String,
+    #[uniffi(default = 1500)]
+    pub tun_mtu: u16,
+    /// Traffic routed around the TUN interface instead of through it. All
+    /// traffic goes through the interface when `None`.
+    #[uniffi(default = None)]
+    pub route_exclusions: Option<RouteExclusions>,
+
+    /// Nameservers as `udp://`, `tls://`, `https://` or `quic://` URLs.
+    /// Replaces the profile's `nameserver` list when not empty.
+    #[uniffi(default = [])]
+    pub nameserver: Vec<String>,
+    /// Bootstrap nameservers, which must use an IP address. Replaces the
+    /// profile's `default-nameserver` list when not empty.
+    #[uniffi(default = [])]
+    pub default_nameserver: Vec<String>,
+    /// Replaces the profile's `fallback` list when not empty
+    #[uniffi(default = [])]
+    pub fallback: Vec<String>,
+    /// Resolvers used when neither this override nor the profile names any.
+    /// Defaults to [`DnsPreset::China`].
+    #[uniffi(default = None)]
+    pub dns_preset: Option<DnsPreset>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Enum)]
+pub enum InstanceState {
+    /// The core is parsing the profile and binding its listeners
+    Starting,
+    /// The controller and mixed port are accepting connections
+    Running,
+    /// The core exited with an error
+    Failed { reason: String },
+    /// The core was shut down or exited cleanly
+    Stopped,
+}
+
+impl InstanceState {
+    fn is_terminal(&self) -> bool {
+        matches!(self, Self::Failed { .. } | Self::Stopped)
+    }
+}
+
+/// Milestones of a core run.
+///
+/// There is no event for the TUN device being attached yet. clash-lib opens
+/// the `fd://` device inside `start` and reports neither success nor
+/// failure, and the app's own sockets bypass the VPN, so the device cannot
+/// be probed from here either. A failure to open it still surfaces as
+/// `CoreExited`.
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Enum)]
+pub enum LifecycleEvent {
+    /// The profile was parsed and the overrides applied
+    ConfigParsed,
+    /// The controller and proxy listeners are accepting connections
+    ListenersBound,
+    /// The core stopped on its own rather than through `shutdown`. `reason` is
+    /// `None` if it returned without an error.
+    CoreExited { reason: Option<String> },
+    /// The background task has finished and released its resources
+    ShutdownComplete,
+}
+
+#[uniffi::export(callback_interface)]
+pub trait LifecycleCallback: Send + Sync {
+    fn on_event(&self, event: LifecycleEvent);
+}
+
+#[derive(Clone, Default)]
+struct Lifecycle(Option<Arc<dyn LifecycleCallback>>);
+
+impl Lifecycle {
+    fn emit(&self, event: LifecycleEvent) {
+        info!("clash-rs lifecycle: {event:?}");
+        if let Some(callback) = &self.0 {
+            callback.on_event(event);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum NetworkTransport {
+    Wifi,
+    Cellular,
+    Ethernet,
+    Other,
+}
+
+/// The uplink the VPN runs over, as reported by Android's
+/// `NetworkCapabilities`
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct NetworkInfo {
+    /// `Network.getNetworkHandle()`, which tells apart two networks of the
+    /// same kind, e.g. after roaming to another Wi-Fi
+    pub network_handle: i64,
+    pub transport: NetworkTransport,
+    pub ipv4_available: bool,
+    pub ipv6_available: bool,
+    pub metered: bool,
+}
+
+/// One run of the core. A reload replaces it while the instance lives on.
+struct CoreTask {
+    token: CancellationToken,
+    handle: JoinHandle<()>,
 }
 
 #[derive(uniffi::Object)]
 pub struct ClashInstance {
-    mixed_port: u16,
+    work_dir: String,
+    mixed_port: AtomicU16,
     cancel_token: CancellationToken,
-    _handle: Option<JoinHandle<eyre::Result<()>>>,
+    state: watch::Sender<InstanceState>,
+    lifecycle: Lifecycle,
+    /// The TUN fd handed over by the app, kept open across reloads and
+    /// closed on shutdown
+    tun_fd: std::sync::Mutex<Option<TunFd>>,
+    /// The last profile that started successfully, re-applied when the
+    /// system DNS changes
+    profile: std::sync::Mutex<(String, ProfileOverride)>,
+    /// Addressing the VPN interface was established with. A reload cannot
+    /// change it, since the interface outlives the core.
+    tun_plan: TunPlan,
+    system_dns: std::sync::Mutex<SystemDns>,
+    network: std::sync::Mutex<Option<NetworkInfo>>,
+    core: Mutex<Option<CoreTask>>,
 }
 
-#[uniffi::export]
+impl ClashInstance {
+    /// Build the config for `profile`, or for the current profile if `None`,
+    /// and replace the running core with it. The running core is left
+    /// untouched if the profile fails to parse. If the new core cannot be
+    /// started, the instance moves to `Failed`.
+    async fn restart(&self, profile: Option<(String, ProfileOverride)>) -> Result<(), ClashError> {
+        if self.cancel_token.is_cancelled() {
+            return Err(ClashError::Cancelled);
+        }
+        // Held from reading the profile until the new one is recorded, so a
+        // concurrent restart cannot start the core on a stale profile
+        let mut core = self.core.lock().await;
+        let (config_path, over) = profile.unwrap_or_else(|| self.profile.lock().unwrap().clone());
+        let system_dns = self.system_dns.lock().unwrap().clone();
+        let (config, mixed_port) = build_config(&config_path, &self.work_dir, &over, &system_dns)?;
+        self.lifecycle.emit(LifecycleEvent::ConfigParsed);
+
+        if let Some(old) = core.take() {
+            old.token.cancel();
+            _ = old.handle.await;
+        }
+        self.mixed_port.store(mixed_port, Ordering::Relaxed);
+        match self.start_core(config) {
+            Ok(task) => {
+                *core = Some(task);
+                *self.profile.lock().unwrap() = (config_path, over);
+                Ok(())
+            }
+            Err(err) => {
+                // The old core is already gone, so nothing is running any
+                // more. Report it like a core that died on its own.
+                let reason = err.to_string();
+                let failed = self.state.send_if_modified(|state| {
+                    if state.is_terminal() {
+                        return false;
+                    }
+                    *state = InstanceState::Failed {
+                        reason: reason.clone(),
+                    };
+                    true
+                });
+                if failed {
+                    self.lifecycle.emit(LifecycleEvent::CoreExited {
+                        reason: Some(reason),
+                    });
+                }
+                Err(err)
+            }
+        }
+    }
+
+    fn start_core(&self, mut config: InternalConfig) -> Result<CoreTask, ClashError> {
+        let fd = match self.tun_fd.lock().unwrap().as_ref() {
+            Some(fd) => core_tun_fd(fd)?,
+            None => return Err(ClashError::Cancelled),
+        };
+        config.tun.device_id = format!("fd://{fd}");
+        self.state.send_replace(InstanceState::Starting);
+
+        let token = self.cancel_token.child_token();
+        tokio::spawn(probe_ready(
+            format!("{}/clash.sock", self.work_dir),
+            self.mixed_port(),
+            self.state.clone(),
+            token.clone(),
+            self.lifecycle.clone(),
+        ));
+        let handle = tokio::spawn(run_core(
+            config,
+            self.work_dir.clone(),
+            token.clone(),
+            self.cancel_token.clone(),
+            self.state.clone(),
+            self.lifecycle.clone(),
+        ));
+
+        Ok(CoreTask { token, handle })
+    }
+}
+
+#[uniffi::export(async_runtime = "tokio")]
 impl ClashInstance {
     /// The mixed HTTP/SOCKS port the proxy is listening on
     pub fn mixed_port(&self) -> u16 {
-        self.mixed_port
+        self.mixed_port.load(Ordering::Relaxed)
     }
 
     /// Shut down clash-rs and cancel the background task
     pub fn shutdown(&self) {
         self.cancel_token.cancel();
         clash_shutdown();
+        // Closing our copy lets the VPN interface go away once the core has
+        // released its own.
+        self.tun_fd.lock().unwrap().take();
+        self.state.send_if_modified(|state| {
+            if state.is_terminal() {
+                return false;
+            }
+            *state = InstanceState::Stopped;
+            true
+        });
         info!("clash-rs shutdown");
     }
+
+    /// Re-parse `config_path`, apply `over` as `run_clash` does and restart
+    /// the core on the new config. The TUN fd given to `run_clash` stays
+    /// open throughout, so the VPN interface survives; `over.tun_fd` is
+    /// ignored. The running core is left untouched if the new profile fails
+    /// to parse. Use `wait_ready` to learn when the new config is live.
+    ///
+    /// Fails with `InvalidOverride` if `over` leads to a different
+    /// [`tun::tun_plan`] than the interface was set up with. Changing the
+    /// TUN addressing, MTU, IPv6 or routes needs a new VPN session.
+    pub async fn reload(
+        &self,
+        config_path: String,
+        over: ProfileOverride,
+    ) -> Result<(), ClashError> {
+        if tun::Addressing::new(&over)?.plan() != self.tun_plan {
+            return Err(ClashError::invalid_override(
+                "reload cannot change the TUN addressing, MTU, IPv6 or routes; restart the VPN",
+            ));
+        }
+        self.restart(Some((config_path.clone(), over))).await?;
+        info!("clash-rs reloaded from {config_path}");
+        Ok(())
+    }
+
+    /// Use the underlying network's resolvers as bootstrap and `system`
+    /// nameservers, and route its search domains to them. Call again on every
+    /// network change; nothing happens unless the settings differ from the
+    /// last call or from the `system_dns` given to `run_clash`.
+    ///
+    /// clash-lib builds its resolvers once when the core starts and cannot
+    /// swap nameservers in a running core, so applying new settings restarts
+    /// the core on the current profile. The TUN fd stays open, but every
+    /// open connection is dropped.
+    pub async fn set_system_dns(&self, dns: SystemDns) -> Result<(), ClashError> {
+        {
+            let mut current = self.system_dns.lock().unwrap();
+            if *current == dns {
+                return Ok(());
+            }
+            *current = dns;
+        }
+        info!("System DNS changed, restarting clash-rs");
+        self.restart(None).await
+    }
+
+    /// Tell the core the uplink changed. When `info` names another network
+    /// than the last report, cached DNS answers and every open connection
+    /// belong to the old uplink, so both are dropped, and proxy health checks
+    /// are re-run in the background. Other changes on the same network, such
+    /// as an IPv6 address arriving late or the metered flag flipping, are
+    /// only recorded, as is the first report after `run_clash`.
+    pub async fn on_network_changed(&self, info: NetworkInfo) -> Result<(), ClashError> {
+        {
+            let mut current = self.network.lock().unwrap();
+            let previous = current.replace(info.clone());
+            let same_uplink =
+                previous.is_none_or(|previous| previous.network_handle == info.network_handle);
+            if same_uplink {
+                return Ok(());
+            }
+        }
+        info!("Network changed: {info:?}");
+
+        // A restart for new system DNS may be under way. Wait for it, so the
+        // controller calls below reach the core that replaces it.
+        let _core = self.core.lock().await;
+        self.wait_ready(READY_TIMEOUT_MS).await?;
+
+        let controller = ClashController::new(format!("{}/clash.sock", self.work_dir));
+        if let Err(err) = controller.flush_dns_cache().await {
+            warn!("Failed to flush DNS cache: {err}");
+        }
+        controller.close_all_connections().await?;
+        tokio::spawn(async move {
+            if let Err(err) = controller.health_check_all().await {
+                warn!("Failed to re-run health checks: {err}");
+            }
+        });
+        Ok(())
+    }
+
+    pub fn state(&self) -> InstanceState {
+        self.state.borrow().clone()
+    }
+
+    /// Wait until the core is accepting connections. Fails with the core's
+    /// own error if it exits first, or `StartFailed` after `timeout_ms`.
+    pub async fn wait_ready(&self, timeout_ms: u64) -> Result<(), ClashError> {
+        let mut rx = self.state.subscribe();
+        let settled = tokio::time::timeout(
+            Duration::from_millis(timeout_ms),
+            rx.wait_for(|state| *state != InstanceState::Starting),
+        )
+        .await
+        .map_err(|_| ClashError::StartFailed {
+            reason: format!("core did not become ready within {timeout_ms} ms"),
+        })?
+        .map_err(|_| ClashError::Cancelled)?
+        .clone();
+
+        match settled {
+            InstanceState::Running => Ok(()),
+            InstanceState::Failed { reason } => Err(ClashError::StartFailed { reason }),
+            InstanceState::Starting | InstanceState::Stopped => Err(ClashError::Cancelled),
+        }
+    }
+
+    /// Forward core and FFI log events at `min_level` or above to `callback`
+    /// until the returned subscription is cancelled
+    pub async fn subscribe_logs(
+        &self,
+        min_level: log::LogLevel,
+        callback: Box<dyn LogCallback>,
+    ) -> Arc<Subscription> {
+        let subscription = Subscription::new();
+        let token = subscription.token();
+        let mut rx = log::subscribe();
+        tokio::spawn(async move {
+            loop {
+                let record = tokio::select! {
+                    _ = token.cancelled() => break,
+                    record = rx.recv() => record,
+                };
+                match record {
+                    Ok(record) if record.level >= min_level => callback.on_log(record),
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+        subscription
+    }
 }
 
 #[unsafe(export_name = "Java_rs_clash_android_MainActivity_javaInit")]
@@ -157,34 +515,94 @@ pub extern "system" fn java_init(
 }
 
 #[uniffi::export]
-fn verify_config(config_path: &str) -> Result<String, EyreError> {
-    let _config = Config::File(config_path.to_string()).try_parse()?;
+fn verify_config(config_path: &str) -> Result<String, ClashError> {
+    let _config = Config::File(config_path.to_string())
+        .try_parse()
+        .map_err(ClashError::config_parse)?;
     Ok("config verified successfully".to_string())
 }
 
-#[uniffi::export(async_runtime = "tokio")]
+/// Start the core on `config_path`. Pass the current network's resolvers as
+/// `system_dns`, so the first `set_system_dns` call does not restart the
+/// core to apply them.
+#[uniffi::export(
+    async_runtime = "tokio",
+    default(lifecycle = None, system_dns = None)
+)]
 async fn run_clash(
     config_path: String,
     work_dir: String,
     over: ProfileOverride,
-) -> Result<Arc<ClashInstance>, EyreError> {
-    std::env::set_current_dir(&work_dir)?;
-    let mut config_def = ConfigDef::try_from(PathBuf::from(config_path.clone()))?;
+    lifecycle: Option<Box<dyn LifecycleCallback>>,
+    system_dns: Option<SystemDns>,
+) -> Result<Arc<ClashInstance>, ClashError> {
+    let lifecycle = Lifecycle(lifecycle.map(Arc::from));
+    // Take ownership of the fd the app detached. The core only ever gets
+    // duplicates, so it can be restarted without closing the VPN interface.
+    let tun_fd = adopt_tun_fd(over.tun_fd);
+    let system_dns = system_dns.unwrap_or_default();
+    let (config, mixed_port) = build_config(&config_path, &work_dir, &over, &system_dns)?;
+    let tun_plan = tun::Addressing::new(&over)?.plan();
+
+    info!("Config path: {config_path}\n\tTUN fd: {}", over.tun_fd);
+    lifecycle.emit(LifecycleEvent::ConfigParsed);
+
+    let (state, _) = watch::channel(InstanceState::Starting);
+    let instance = Arc::new(ClashInstance {
+        work_dir,
+        mixed_port: AtomicU16::new(mixed_port),
+        cancel_token: CancellationToken::new(),
+        state,
+        lifecycle,
+        tun_fd: std::sync::Mutex::new(Some(tun_fd)),
+        profile: std::sync::Mutex::new((config_path, over)),
+        tun_plan,
+        system_dns: std::sync::Mutex::new(system_dns),
+        network: std::sync::Mutex::new(None),
+        core: Mutex::new(None),
+    });
+    let core = instance.start_core(config)?;
+    *instance.core.lock().await = Some(core);
+
+    Ok(instance)
+}
+
+/// Parse the profile and apply the app's overrides on top of it. Returns the
+/// config together with the effective mixed port.
+fn build_config(
+    config_path: &str,
+    work_dir: &str,
+    over: &ProfileOverride,
+    system_dns: &SystemDns,
+) -> Result<(InternalConfig, u16), ClashError> {
+    std::env::set_current_dir(work_dir)?;
+    let mut config_def =
+        ConfigDef::try_from(PathBuf::from(config_path)).map_err(ClashError::config_parse)?;
     let mixed_port = config_def.mixed_port.get_or_insert(Port(over.mixed_port)).0;
     config_def.port = config_def.port.or_else(|| over.http_port.map(Port));
     config_def.socks_port = config_def.socks_port.or_else(|| over.socks_port.map(Port));
+    nameserver::apply_overrides(&mut config_def, over)?;
+    nameserver::apply_system_dns(&mut config_def, system_dns);
+
+    let addressing = tun::Addressing::new(over)?;
 
-    let mut config = Config::Def(config_def).try_parse()?;
+    let mut config = Config::Def(config_def)
+        .try_parse()
+        .map_err(ClashError::config_parse)?;
     config.tun = TunConfig {
         enable: true,
-        device_id: format!("fd://{}", over.tun_fd),
+        // Filled in by `ClashInstance::start_core` with a fresh duplicate of
+        // the TUN fd for every core run
+        device_id: String::new(),
         route_all: false,
         routes: Vec::new(),
-        gateway: ipnet::Ipv4Net::new(Ipv4Addr::new(10, 0, 0, 1), 30)?,
-        gateway_v6: None,
-        mtu: None,
+        gateway: addressing.gateway,
+        gateway_v6: addressing.gateway_v6,
+        mtu: Some(addressing.mtu),
         so_mark: None,
         route_table: 0,
+        // Catches port 53 traffic of both families, so resolvers the app
+        // advertises on the IPv6 side of the interface are answered too
         dns_hijack: true,
     };
 
@@ -198,65 +616,37 @@ async fn run_clash(
     };
 
     config.general.ipv6 = over.ipv6;
-    // Bootstrap nameservers. These are built with no parent resolver, so the
-    // host must be a literal IP (a `Host::Domain` would hit the resolve path
-    // and fail).
-    let udp_bootstrap = |host: Host| NameServer {
-        net: DNSNetMode::Udp,
-        host,
-        port: 53,
-        interface: None,
-        proxy: None,
-    };
+    let preset = over.dns_preset.unwrap_or_default();
     let mut default_nameserver = if config.dns.default_nameserver.is_empty() {
-        vec![
-            udp_bootstrap(Host::Ipv4(Ipv4Addr::new(223, 5, 5, 5))), // AliDNS
-            udp_bootstrap(Host::Ipv4(Ipv4Addr::new(223, 6, 6, 6))), // AliDNS
-            udp_bootstrap(Host::Ipv4(Ipv4Addr::new(8, 8, 8, 8))),   // Google
-        ]
+        preset.default_nameserver()
     } else {
         config.dns.default_nameserver.clone()
     };
-    // Always append IPv6 bootstrap servers so resolution works on IPv6-only
-    // uplinks (e.g. 464XLAT cellular), where the interface has no IPv4 address
-    // and the IPv4 servers are unreachable. We append unconditionally — even
-    // when the profile supplies its own `default-nameserver` — because such
-    // profiles are almost always IPv4-only and would otherwise have no usable
-    // bootstrap on an IPv6-only network.
-    let ipv6_bootstrap = [
-        Host::Ipv6(Ipv6Addr::new(0x2400, 0x3200, 0, 0, 0, 0, 0, 1)), // AliDNS
-        Host::Ipv6(Ipv6Addr::new(0x2400, 0x3200, 0xbaba, 0, 0, 0, 0, 1)), // AliDNS
-        Host::Ipv6(Ipv6Addr::new(0x2001, 0x4860, 0x4860, 0, 0, 0, 0, 0x8888)), // Google
-    ];
-    for host in ipv6_bootstrap {
+    // Append IPv6 bootstrap servers so resolution works on IPv6-only uplinks
+    // (e.g. 464XLAT cellular), where the interface has no IPv4 address and
+    // the IPv4 servers are unreachable. We append even when the profile
+    // supplies its own `default-nameserver`, because such profiles are almost
+    // always IPv4-only and would otherwise have no usable bootstrap on an
+    // IPv6-only network. A list set explicitly by the app is left alone.
+    if over.default_nameserver.is_empty() {
+        for host in preset.ipv6_bootstrap() {
+            if !default_nameserver.iter().any(|ns| ns.host == host) {
+                default_nameserver.push(udp_bootstrap(host));
+            }
+        }
+    }
+    // The network's own resolvers are the most likely to be reachable
+    for ip in system_dns.server_ips().into_iter().rev() {
+        let host = match ip {
+            IpAddr::V4(ip) => Host::Ipv4(ip),
+            IpAddr::V6(ip) => Host::Ipv6(ip),
+        };
         if !default_nameserver.iter().any(|ns| ns.host == host) {
-            default_nameserver.push(udp_bootstrap(host));
+            default_nameserver.insert(0, udp_bootstrap(host));
         }
     }
     let nameserver = if config.dns.nameserver.is_empty() {
-        vec![
-            NameServer {
-                net: DNSNetMode::DoT,
-                host: Host::Ipv4(Ipv4Addr::new(223, 5, 5, 5)),
-                port: 853,
-                interface: None,
-                proxy: None,
-            },
-            NameServer {
-                net: DNSNetMode::DoT,
-                host: Host::Ipv4(Ipv4Addr::new(223, 6, 6, 6)),
-                port: 853,
-                interface: None,
-                proxy: None,
-            },
-            NameServer {
-                net: DNSNetMode::DoT,
-                host: Host::Ipv4(Ipv4Addr::new(1, 1, 1, 1)),
-                port: 853,
-                interface: None,
-                proxy: None,
-            },
-        ]
+        preset.nameserver()
     } else {
         config.dns.nameserver.clone()
     };
@@ -277,39 +667,145 @@ async fn run_clash(
     };
     if over.fake_ip {
         config.dns.enhance_mode = DNSMode::FakeIp;
-        config.dns.fake_ip_range = over.fake_ip_range.parse()?;
+        config.dns.fake_ip_range = over
+            .fake_ip_range
+            .parse()
+            .map_err(ClashError::invalid_override)?;
         config.dns.store_fake_ip = true;
     } else {
         config.dns.enhance_mode = DNSMode::Normal;
     }
 
-    info!("Config path: {config_path}\n\tTUN fd: {}", over.tun_fd);
+    Ok((config, mixed_port))
+}
 
-    let cancel_token = CancellationToken::new();
-    let token = cancel_token.clone();
-    let handle: JoinHandle<eyre::Result<()>> = tokio::spawn(async move {
-        let (log_tx, _) = tokio::sync::broadcast::channel(100);
-        info!("Starting clash-rs");
-        let start_result = start(config, work_dir, log_tx, token.child_token());
-        tokio::select! {
-            result = start_result => {
-                if let Err(err) = result {
-                    error!("clash-rs start error: {:#}", eyre::eyre!(err));
-                }
-            }
-            _ = token.cancelled() => {
-                info!("clash-rs cancelled");
+/// Drive one run of the core until it exits or `token` is cancelled. A
+/// cancelled `token` with a live `instance_token` means the run is being
+/// replaced by a reload, which is not reported as a shutdown.
+async fn run_core(
+    config: InternalConfig,
+    work_dir: String,
+    token: CancellationToken,
+    instance_token: CancellationToken,
+    state: watch::Sender<InstanceState>,
+    lifecycle: Lifecycle,
+) {
+    let (log_tx, _) = tokio::sync::broadcast::channel(100);
+    info!("Starting clash-rs");
+    let start_result = start(config, work_dir, log_tx, token.child_token());
+    tokio::pin!(start_result);
+    let exited = tokio::select! {
+        result = &mut start_result => Some(result),
+        _ = token.cancelled() => None,
+    };
+    if exited.is_none() {
+        // Wait for the core to close its listeners, so that the next run can
+        // bind the same ports
+        _ = (&mut start_result).await;
+    }
+    // `shutdown` and reloads cancel the token before they stop the core, so
+    // a core that returns with the token cancelled did not exit on its own,
+    // even if it won the race against `token.cancelled()`
+    let final_state = match exited {
+        Some(Ok(())) if !token.is_cancelled() => {
+            lifecycle.emit(LifecycleEvent::CoreExited { reason: None });
+            InstanceState::Stopped
+        }
+        Some(Err(err)) if !token.is_cancelled() => {
+            let err = eyre::eyre!(err);
+            error!("clash-rs start error: {err:#}");
+            let reason = format!("{err:#}");
+            lifecycle.emit(LifecycleEvent::CoreExited {
+                reason: Some(reason.clone()),
+            });
+            InstanceState::Failed { reason }
+        }
+        _ => {
+            if !instance_token.is_cancelled() {
+                info!("clash-rs stopped for reload");
+                return;
             }
+            info!("clash-rs cancelled");
+            InstanceState::Stopped
         }
-        info!("Quitting clash-rs");
-        Ok(())
+    };
+    state.send_if_modified(|state| {
+        if state.is_terminal() {
+            return false;
+        }
+        *state = final_state;
+        true
     });
+    info!("Quitting clash-rs");
+    lifecycle.emit(LifecycleEvent::ShutdownComplete);
+}
+
+#[cfg(unix)]
+type TunFd = std::os::fd::OwnedFd;
+#[cfg(not(unix))]
+type TunFd = i32;
 
-    Ok(Arc::new(ClashInstance {
-        mixed_port,
-        cancel_token,
-        _handle: Some(handle),
-    }))
+#[cfg(unix)]
+fn adopt_tun_fd(fd: i32) -> TunFd {
+    use std::os::fd::FromRawFd;
+    // SAFETY: the app detaches the fd from its ParcelFileDescriptor before
+    // handing it over, so nothing else owns or closes it.
+    unsafe { TunFd::from_raw_fd(fd) }
+}
+
+#[cfg(not(unix))]
+fn adopt_tun_fd(fd: i32) -> TunFd {
+    fd
+}
+
+/// A duplicate of the TUN fd for the core to own and close
+#[cfg(unix)]
+fn core_tun_fd(fd: &TunFd) -> Result<i32, ClashError> {
+    use std::os::fd::IntoRawFd;
+    Ok(fd.try_clone()?.into_raw_fd())
+}
+
+#[cfg(not(unix))]
+fn core_tun_fd(fd: &TunFd) -> Result<i32, ClashError> {
+    Ok(*fd)
+}
+
+/// Mark the instance `Running` once the controller answers and the mixed
+/// port accepts connections. clash-rs binds every listener concurrently and
+/// aborts `start` if any of them fails, so both being reachable while the
+/// core is still alive means startup succeeded.
+async fn probe_ready(
+    socket_path: String,
+    mixed_port: u16,
+    state: watch::Sender<InstanceState>,
+    token: CancellationToken,
+    lifecycle: Lifecycle,
+) {
+    let controller = ClashController::new(socket_path);
+    loop {
+        if token.is_cancelled() || *state.borrow() != InstanceState::Starting {
+            return;
+        }
+        let listening = controller.get_configs().await.is_ok()
+            && TcpStream::connect((Ipv4Addr::LOCALHOST, mixed_port))
+                .await
+                .is_ok();
+        if listening {
+            let ready = state.send_if_modified(|state| {
+                if *state != InstanceState::Starting {
+                    return false;
+                }
+                *state = InstanceState::Running;
+                true
+            });
+            if ready {
+                lifecycle.emit(LifecycleEvent::ListenersBound);
+            }
+            info!("clash-rs is ready");
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
 }
 
 uniffi::setup_scaffolding!("clash_android_ffi");
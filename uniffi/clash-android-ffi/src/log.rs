@@ -1,8 +1,115 @@
+use std::{
+    fmt::Write as _,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use once_cell::sync::Lazy;
+use tokio::sync::broadcast;
+use tracing::{
+    Event, Subscriber,
+    field::{Field, Visit},
+};
 use tracing_error::ErrorLayer;
 use tracing_subscriber::EnvFilter;
+use tracing_subscriber::layer::{Context, Layer};
 #[allow(unused_imports)]
 use tracing_subscriber::{filter::LevelFilter, fmt::format::FmtSpan, prelude::*};
 
+/// Fan-out of every log event that passes the global filter. Sending is
+/// skipped entirely while nobody is subscribed.
+static LOG_TX: Lazy<broadcast::Sender<LogRecord>> = Lazy::new(|| broadcast::channel(256).0);
+
+/// Ordered from most to least verbose, so `level >= min_level` selects
+/// everything at least as severe as `min_level`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, uniffi::Enum)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl From<tracing::Level> for LogLevel {
+    fn from(level: tracing::Level) -> Self {
+        match level {
+            tracing::Level::TRACE => LogLevel::Trace,
+            tracing::Level::DEBUG => LogLevel::Debug,
+            tracing::Level::INFO => LogLevel::Info,
+            tracing::Level::WARN => LogLevel::Warn,
+            tracing::Level::ERROR => LogLevel::Error,
+        }
+    }
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct LogRecord {
+    /// Unix timestamp in milliseconds
+    pub time: i64,
+    pub level: LogLevel,
+    /// Module path of the emitting code, e.g. `clash_lib::app::dispatcher`
+    pub target: String,
+    pub message: String,
+}
+
+#[uniffi::export(callback_interface)]
+pub trait LogCallback: Send + Sync {
+    fn on_log(&self, record: LogRecord);
+}
+
+pub(crate) fn subscribe() -> broadcast::Receiver<LogRecord> {
+    LOG_TX.subscribe()
+}
+
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            _ = write!(self.message, "{value:?}");
+        } else {
+            _ = write!(self.fields, " {}={value:?}", field.name());
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            _ = write!(self.fields, " {}={value}", field.name());
+        }
+    }
+}
+
+struct BroadcastLayer;
+
+impl<S: Subscriber> Layer<S> for BroadcastLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if LOG_TX.receiver_count() == 0 {
+            return;
+        }
+
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let metadata = event.metadata();
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or_default();
+
+        _ = LOG_TX.send(LogRecord {
+            time,
+            level: (*metadata.level()).into(),
+            target: metadata.target().to_string(),
+            message: visitor.message + &visitor.fields,
+        });
+    }
+}
+
 pub(crate) fn init_logger(level: LevelFilter) {
     let filter = EnvFilter::from_default_env()
         .add_directive(format!("clash={}", level).parse().unwrap())
@@ -22,6 +129,7 @@ pub(crate) fn init_logger(level: LevelFilter) {
 
         tracing_subscriber::registry()
             .with(android_layer)
+            .with(BroadcastLayer)
             .with(filter)
             .with(ErrorLayer::default())
             .init();
@@ -29,6 +137,7 @@ pub(crate) fn init_logger(level: LevelFilter) {
 
     #[cfg(not(target_os = "android"))]
     tracing_subscriber::registry()
+        .with(BroadcastLayer)
         .with(filter)
         .with(ErrorLayer::default())
         .init();
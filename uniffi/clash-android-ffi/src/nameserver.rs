@@ -0,0 +1,238 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use clash_lib::{
+    app::dns::config::{DNSNetMode, NameServer},
+    config::def::Config as ConfigDef,
+};
+use tracing::warn;
+use url::{Host, Url};
+
+use crate::{ProfileOverride, error::ClashError};
+
+const SUPPORTED_SCHEMES: [&str; 4] = ["udp", "tls", "https", "quic"];
+
+/// Resolver settings of the network the VPN currently runs over, as
+/// reported by Android's `LinkProperties`
+#[derive(Debug, Clone, Default, PartialEq, Eq, uniffi::Record)]
+pub struct SystemDns {
+    /// Resolver IP addresses. A `%scope` suffix on link-local addresses is
+    /// accepted and ignored.
+    pub servers: Vec<String>,
+    /// Search domains, e.g. `corp.example.com`
+    pub search_domains: Vec<String>,
+}
+
+impl SystemDns {
+    /// The resolvers that parse as IP addresses; others are logged and
+    /// skipped so one odd entry does not lose the rest.
+    pub(crate) fn server_ips(&self) -> Vec<IpAddr> {
+        self.servers
+            .iter()
+            .filter_map(|server| {
+                let addr = server.split('%').next().unwrap_or(server);
+                match addr.parse() {
+                    Ok(ip) => Some(ip),
+                    Err(_) => {
+                        warn!("Ignoring invalid system DNS server: {server}");
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+/// Built-in resolver sets used when neither the app nor the profile names
+/// any nameservers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, uniffi::Enum)]
+pub enum DnsPreset {
+    /// AliDNS, with Google and Cloudflare as backups
+    #[default]
+    China,
+    /// Cloudflare and Google
+    Global,
+}
+
+impl DnsPreset {
+    /// IPv4 bootstrap resolvers, used to resolve the hostnames of the
+    /// encrypted nameservers
+    pub(crate) fn default_nameserver(self) -> Vec<NameServer> {
+        let hosts = match self {
+            DnsPreset::China => vec![
+                Ipv4Addr::new(223, 5, 5, 5), // AliDNS
+                Ipv4Addr::new(223, 6, 6, 6), // AliDNS
+                Ipv4Addr::new(8, 8, 8, 8),   // Google
+            ],
+            DnsPreset::Global => vec![
+                Ipv4Addr::new(1, 1, 1, 1), // Cloudflare
+                Ipv4Addr::new(8, 8, 8, 8), // Google
+            ],
+        };
+        hosts
+            .into_iter()
+            .map(|ip| udp_bootstrap(Host::Ipv4(ip)))
+            .collect()
+    }
+
+    /// IPv6 bootstrap resolvers for IPv6-only uplinks
+    pub(crate) fn ipv6_bootstrap(self) -> Vec<Host> {
+        let hosts = match self {
+            DnsPreset::China => vec![
+                Ipv6Addr::new(0x2400, 0x3200, 0, 0, 0, 0, 0, 1), // AliDNS
+                Ipv6Addr::new(0x2400, 0x3200, 0xbaba, 0, 0, 0, 0, 1), // AliDNS
+                Ipv6Addr::new(0x2001, 0x4860, 0x4860, 0, 0, 0, 0, 0x8888), // Google
+            ],
+            DnsPreset::Global => vec![
+                Ipv6Addr::new(0x2606, 0x4700, 0x4700, 0, 0, 0, 0, 0x1111), // Cloudflare
+                Ipv6Addr::new(0x2001, 0x4860, 0x4860, 0, 0, 0, 0, 0x8888), // Google
+            ],
+        };
+        hosts.into_iter().map(Host::Ipv6).collect()
+    }
+
+    /// DNS-over-TLS resolvers
+    pub(crate) fn nameserver(self) -> Vec<NameServer> {
+        let hosts = match self {
+            DnsPreset::China => vec![
+                Ipv4Addr::new(223, 5, 5, 5), // AliDNS
+                Ipv4Addr::new(223, 6, 6, 6), // AliDNS
+                Ipv4Addr::new(1, 1, 1, 1),   // Cloudflare
+            ],
+            DnsPreset::Global => vec![
+                Ipv4Addr::new(1, 1, 1, 1), // Cloudflare
+                Ipv4Addr::new(8, 8, 8, 8), // Google
+            ],
+        };
+        hosts
+            .into_iter()
+            .map(|ip| NameServer {
+                net: DNSNetMode::DoT,
+                host: Host::Ipv4(ip),
+                port: 853,
+                interface: None,
+                proxy: None,
+            })
+            .collect()
+    }
+}
+
+/// Bootstrap nameservers are built with no parent resolver, so the host must
+/// be a literal IP (a `Host::Domain` would hit the resolve path and fail).
+pub(crate) fn udp_bootstrap(host: Host) -> NameServer {
+    NameServer {
+        net: DNSNetMode::Udp,
+        host,
+        port: 53,
+        interface: None,
+        proxy: None,
+    }
+}
+
+/// Replace the profile's resolver lists with the ones set in `over`. This
+/// runs on the raw profile so the core parses the URLs exactly as it would
+/// a `dns:` section.
+pub(crate) fn apply_overrides(
+    config_def: &mut ConfigDef,
+    over: &ProfileOverride,
+) -> Result<(), ClashError> {
+    for url in over.nameserver.iter().chain(&over.fallback) {
+        validate(url, false)?;
+    }
+    for url in &over.default_nameserver {
+        validate(url, true)?;
+    }
+
+    if !over.nameserver.is_empty() {
+        config_def.dns.nameserver = over.nameserver.clone();
+    }
+    if !over.default_nameserver.is_empty() {
+        config_def.dns.default_nameserver = over.default_nameserver.clone();
+    }
+    if !over.fallback.is_empty() {
+        config_def.dns.fallback = over.fallback.clone();
+    }
+    Ok(())
+}
+
+/// Point the profile at the network's own resolvers: `system` entries in
+/// `nameserver` and `fallback` expand to them, and names under the search
+/// domains are sent to them so split-horizon names on corporate networks
+/// resolve. A policy the profile already sets for a domain wins.
+pub(crate) fn apply_system_dns(config_def: &mut ConfigDef, system: &SystemDns) {
+    let servers: Vec<String> = system
+        .server_ips()
+        .into_iter()
+        .map(|ip| format!("udp://{}", SocketAddr::new(ip, 53)))
+        .collect();
+    let Some(primary) = servers.first() else {
+        return;
+    };
+
+    let expand = |list: &mut Vec<String>| {
+        if list.iter().any(|ns| is_system(ns)) {
+            let expanded = list
+                .drain(..)
+                .flat_map(|ns| {
+                    if is_system(&ns) {
+                        servers.clone()
+                    } else {
+                        vec![ns]
+                    }
+                })
+                .collect();
+            *list = expanded;
+        }
+    };
+    expand(&mut config_def.dns.nameserver);
+    expand(&mut config_def.dns.fallback);
+
+    for domain in &system.search_domains {
+        let domain = domain.trim_matches('.');
+        if domain.is_empty() {
+            continue;
+        }
+        config_def
+            .dns
+            .nameserver_policy
+            .entry(format!("+.{domain}"))
+            .or_insert_with(|| primary.clone());
+    }
+}
+
+fn is_system(nameserver: &str) -> bool {
+    matches!(nameserver, "system" | "system://")
+}
+
+/// Accept `udp://`, `tls://`, `https://` and `quic://` URLs, or a bare
+/// `ip[:port]` for plain UDP. Bootstrap resolvers must use an IP literal.
+fn validate(url: &str, require_ip: bool) -> Result<(), ClashError> {
+    if !url.contains("://") {
+        return if url.parse::<IpAddr>().is_ok() || url.parse::<SocketAddr>().is_ok() {
+            Ok(())
+        } else {
+            Err(ClashError::invalid_override(format!(
+                "nameserver {url} needs a scheme ({}) or must be an IP address",
+                SUPPORTED_SCHEMES.join(", ")
+            )))
+        };
+    }
+
+    let parsed = Url::parse(url)
+        .map_err(|e| ClashError::invalid_override(format!("nameserver {url}: {e}")))?;
+    if !SUPPORTED_SCHEMES.contains(&parsed.scheme()) {
+        return Err(ClashError::invalid_override(format!(
+            "nameserver {url}: unsupported scheme {}, expected one of {}",
+            parsed.scheme(),
+            SUPPORTED_SCHEMES.join(", ")
+        )));
+    }
+    match parsed.host() {
+        None => Err(ClashError::invalid_override(format!(
+            "nameserver {url} has no host"
+        ))),
+        Some(Host::Domain(_)) if require_ip => Err(ClashError::invalid_override(format!(
+            "default nameserver {url} must use an IP address"
+        ))),
+        Some(_) => Ok(()),
+    }
+}
@@ -0,0 +1,316 @@
+use std::{
+    net::{Ipv4Addr, Ipv6Addr},
+    path::PathBuf,
+};
+
+use clash_lib::config::def::Config as ConfigDef;
+use ipnet::{IpNet, Ipv4Net, Ipv4Subnets, Ipv6Net, Ipv6Subnets};
+
+use crate::{error::ClashError, tun::IpPrefix};
+
+/// Destinations that never leave the device or the local link
+const PRIVATE_V4: [(Ipv4Addr, u8); 7] = [
+    (Ipv4Addr::new(10, 0, 0, 0), 8),
+    (Ipv4Addr::new(127, 0, 0, 0), 8),
+    (Ipv4Addr::new(169, 254, 0, 0), 16),
+    (Ipv4Addr::new(172, 16, 0, 0), 12),
+    (Ipv4Addr::new(192, 168, 0, 0), 16),
+    (Ipv4Addr::new(224, 0, 0, 0), 4),
+    (Ipv4Addr::new(255, 255, 255, 255), 32),
+];
+
+const PRIVATE_V6: [(Ipv6Addr, u8); 4] = [
+    (Ipv6Addr::LOCALHOST, 128),
+    (Ipv6Addr::new(0xfc00, 0, 0, 0, 0, 0, 0, 0), 7),
+    (Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 0), 10),
+    (Ipv6Addr::new(0xff00, 0, 0, 0, 0, 0, 0, 0), 8),
+];
+
+/// Traffic to keep out of the tunnel
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct RouteExclusions {
+    /// Loopback, link-local, multicast and the RFC 1918 and unique local
+    /// ranges
+    #[uniffi(default = true)]
+    pub private: bool,
+    /// Further CIDRs, such as the subnets of the current LAN or entries the
+    /// user added
+    #[uniffi(default = [])]
+    pub cidrs: Vec<String>,
+    /// Profile whose `IP-CIDR` and `IP-CIDR6` rules with a `DIRECT` target
+    /// are excluded as well
+    #[uniffi(default = None)]
+    pub direct_rules_profile: Option<String>,
+}
+
+/// The smallest set of routes that covers every IPv4 and IPv6 address
+/// outside `exclusions`, IPv4 first. `tun_plan` additionally keeps the TUN
+/// subnets and the fake-ip range in its routes.
+#[uniffi::export]
+pub fn included_routes(exclusions: RouteExclusions) -> Result<Vec<IpPrefix>, ClashError> {
+    let (v4, v6) = exclusions.included(&[], &[])?;
+    Ok(v4
+        .into_iter()
+        .map(IpPrefix::from)
+        .chain(v6.into_iter().map(IpPrefix::from))
+        .collect())
+}
+
+impl RouteExclusions {
+    /// Routes for everything outside the exclusions, plus `keep_v4` and
+    /// `keep_v6` even where an exclusion covers them
+    pub(crate) fn included(
+        &self,
+        keep_v4: &[Ipv4Net],
+        keep_v6: &[Ipv6Net],
+    ) -> Result<(Vec<Ipv4Net>, Vec<Ipv6Net>), ClashError> {
+        let (mut v4, mut v6) = (Vec::new(), Vec::new());
+        let mut add = |net: IpNet| match net {
+            IpNet::V4(net) => v4.push(net),
+            IpNet::V6(net) => v6.push(net),
+        };
+
+        if self.private {
+            for (addr, len) in PRIVATE_V4 {
+                add(Ipv4Net::new(addr, len).unwrap().into());
+            }
+            for (addr, len) in PRIVATE_V6 {
+                add(Ipv6Net::new(addr, len).unwrap().into());
+            }
+        }
+        for cidr in &self.cidrs {
+            add(parse_cidr(cidr).ok_or_else(|| {
+                ClashError::invalid_override(format!(
+                    "excluded route {cidr} is not a CIDR or IP address"
+                ))
+            })?);
+        }
+        if let Some(profile) = &self.direct_rules_profile {
+            let config_def =
+                ConfigDef::try_from(PathBuf::from(profile)).map_err(ClashError::config_parse)?;
+            for net in direct_cidrs(&config_def.rule) {
+                add(net);
+            }
+        }
+
+        let mut routes_v4 = complement_v4(&v4);
+        if !keep_v4.is_empty() {
+            routes_v4.extend_from_slice(keep_v4);
+            routes_v4 = Ipv4Net::aggregate(&routes_v4);
+        }
+        let mut routes_v6 = complement_v6(&v6);
+        if !keep_v6.is_empty() {
+            routes_v6.extend_from_slice(keep_v6);
+            routes_v6 = Ipv6Net::aggregate(&routes_v6);
+        }
+        Ok((routes_v4, routes_v6))
+    }
+}
+
+/// Accepts `addr/len` or a bare address; host bits are ignored
+fn parse_cidr(cidr: &str) -> Option<IpNet> {
+    let cidr = cidr.trim();
+    cidr.parse::<IpNet>()
+        .or_else(|_| cidr.parse::<std::net::IpAddr>().map(IpNet::from))
+        .ok()
+        .map(|net| net.trunc())
+}
+
+/// Destinations of rules such as `IP-CIDR,192.168.0.0/16,DIRECT,no-resolve`
+fn direct_cidrs(rules: &[String]) -> impl Iterator<Item = IpNet> + '_ {
+    rules.iter().filter_map(|rule| {
+        let mut parts = rule.split(',').map(str::trim);
+        let kind = parts.next()?;
+        let payload = parts.next()?;
+        let target = parts.next()?;
+        (matches!(kind, "IP-CIDR" | "IP-CIDR6") && target == "DIRECT")
+            .then(|| parse_cidr(payload))
+            .flatten()
+    })
+}
+
+pub(crate) fn complement_v4(excluded: &[Ipv4Net]) -> Vec<Ipv4Net> {
+    let mut next = Some(Ipv4Addr::UNSPECIFIED);
+    let mut routes = Vec::new();
+    for net in Ipv4Net::aggregate(&excluded.to_vec()) {
+        let Some(start) = next else { break };
+        if start < net.network() {
+            let end = Ipv4Addr::from(u32::from(net.network()) - 1);
+            routes.extend(Ipv4Subnets::new(start, end, 0));
+        }
+        next = u32::from(net.broadcast())
+            .checked_add(1)
+            .map(Ipv4Addr::from);
+    }
+    if let Some(start) = next {
+        routes.extend(Ipv4Subnets::new(start, Ipv4Addr::BROADCAST, 0));
+    }
+    routes
+}
+
+pub(crate) fn complement_v6(excluded: &[Ipv6Net]) -> Vec<Ipv6Net> {
+    let mut next = Some(Ipv6Addr::UNSPECIFIED);
+    let mut routes = Vec::new();
+    for net in Ipv6Net::aggregate(&excluded.to_vec()) {
+        let Some(start) = next else { break };
+        if start < net.network() {
+            let end = Ipv6Addr::from(u128::from(net.network()) - 1);
+            routes.extend(Ipv6Subnets::new(start, end, 0));
+        }
+        next = u128::from(net.broadcast())
+            .checked_add(1)
+            .map(Ipv6Addr::from);
+    }
+    if let Some(start) = next {
+        routes.extend(Ipv6Subnets::new(start, Ipv6Addr::from(u128::MAX), 0));
+    }
+    routes
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn v4_net() -> impl Strategy<Value = Ipv4Net> {
+        (any::<u32>(), 0u8..=32)
+            .prop_map(|(addr, len)| Ipv4Net::new(Ipv4Addr::from(addr), len).unwrap().trunc())
+    }
+
+    fn v6_net() -> impl Strategy<Value = Ipv6Net> {
+        (any::<u128>(), 0u8..=128)
+            .prop_map(|(addr, len)| Ipv6Net::new(Ipv6Addr::from(addr), len).unwrap().trunc())
+    }
+
+    /// Addresses right at and around the edges of each excluded network,
+    /// where an off-by-one would show
+    fn edges(excluded: &[(u128, u128)], max: u128) -> Vec<u128> {
+        excluded
+            .iter()
+            .flat_map(|&(start, end)| {
+                [
+                    start.checked_sub(1),
+                    Some(start),
+                    Some(end),
+                    end.checked_add(1).filter(|&a| a <= max),
+                ]
+            })
+            .flatten()
+            .collect()
+    }
+
+    fn assert_complement(excluded: &[(u128, u128)], routes: &[(u128, u128)], probes: &[u128]) {
+        for &addr in probes {
+            let is_excluded = excluded.iter().any(|&(s, e)| s <= addr && addr <= e);
+            let routed = routes
+                .iter()
+                .filter(|&&(s, e)| s <= addr && addr <= e)
+                .count();
+            assert_eq!(routed, usize::from(!is_excluded), "address {addr:#x}");
+        }
+    }
+
+    /// A minimal cover never has two routes that are the halves of one
+    /// larger prefix
+    fn assert_no_siblings(routes: &[(u128, u8)], bits: u8) {
+        for (i, &(a, len)) in routes.iter().enumerate() {
+            if len == 0 {
+                continue;
+            }
+            let sibling = a ^ (1u128 << (bits - len));
+            assert!(
+                !routes[i + 1..].contains(&(sibling, len)),
+                "{a:#x}/{len} has its sibling in the result"
+            );
+        }
+    }
+
+    #[test]
+    fn nothing_excluded_routes_everything() {
+        assert_eq!(complement_v4(&[]), vec![Ipv4Net::default()]);
+        assert_eq!(complement_v6(&[]), vec![Ipv6Net::default()]);
+    }
+
+    #[test]
+    fn everything_excluded_routes_nothing() {
+        assert!(complement_v4(&[Ipv4Net::default()]).is_empty());
+        assert!(complement_v6(&[Ipv6Net::default()]).is_empty());
+    }
+
+    #[test]
+    fn excluding_one_half_routes_the_other() {
+        let lower: Ipv4Net = "0.0.0.0/1".parse().unwrap();
+        assert_eq!(
+            complement_v4(&[lower]),
+            vec!["128.0.0.0/1".parse().unwrap()]
+        );
+    }
+
+    #[test]
+    fn direct_rules_are_picked_up() {
+        let rules = [
+            "IP-CIDR,192.168.0.0/16,DIRECT,no-resolve",
+            "IP-CIDR6, 2001:db8::/32 , DIRECT",
+            "IP-CIDR,1.1.1.0/24,Proxy",
+            "DOMAIN-SUFFIX,example.com,DIRECT",
+            "MATCH,DIRECT",
+        ]
+        .map(String::from);
+        assert_eq!(
+            direct_cidrs(&rules).collect::<Vec<_>>(),
+            vec![
+                "192.168.0.0/16".parse::<IpNet>().unwrap(),
+                "2001:db8::/32".parse().unwrap(),
+            ]
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn v4_routes_are_the_exact_minimal_complement(
+            excluded in prop::collection::vec(v4_net(), 0..16),
+            samples in prop::collection::vec(any::<u32>(), 64),
+        ) {
+            let routes = complement_v4(&excluded);
+            let ranges = |nets: &[Ipv4Net]| -> Vec<(u128, u128)> {
+                nets.iter()
+                    .map(|n| (u32::from(n.network()).into(), u32::from(n.broadcast()).into()))
+                    .collect()
+            };
+            let excluded_ranges = ranges(&excluded);
+            let mut probes = edges(&excluded_ranges, u32::MAX.into());
+            probes.extend(samples.into_iter().map(u128::from));
+            assert_complement(&excluded_ranges, &ranges(&routes), &probes);
+
+            let prefixes: Vec<_> = routes
+                .iter()
+                .map(|n| (u32::from(n.network()).into(), n.prefix_len()))
+                .collect();
+            assert_no_siblings(&prefixes, 32);
+        }
+
+        #[test]
+        fn v6_routes_are_the_exact_minimal_complement(
+            excluded in prop::collection::vec(v6_net(), 0..16),
+            samples in prop::collection::vec(any::<u128>(), 64),
+        ) {
+            let routes = complement_v6(&excluded);
+            let ranges = |nets: &[Ipv6Net]| -> Vec<(u128, u128)> {
+                nets.iter()
+                    .map(|n| (n.network().into(), n.broadcast().into()))
+                    .collect()
+            };
+            let excluded_ranges = ranges(&excluded);
+            let mut probes = edges(&excluded_ranges, u128::MAX);
+            probes.extend(samples);
+            assert_complement(&excluded_ranges, &ranges(&routes), &probes);
+
+            let prefixes: Vec<_> = routes
+                .iter()
+                .map(|n| (n.network().into(), n.prefix_len()))
+                .collect();
+            assert_no_siblings(&prefixes, 128);
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use reqwest::header::{CONTENT_DISPOSITION, HeaderMap};
+
+/// Metadata subscription providers attach to a profile response
+#[derive(Debug, Clone, Default, PartialEq, Eq, uniffi::Record)]
+pub struct SubscriptionInfo {
+    /// Bytes uploaded in the current billing period
+    pub upload: Option<u64>,
+    /// Bytes downloaded in the current billing period
+    pub download: Option<u64>,
+    /// Traffic quota in bytes
+    pub total: Option<u64>,
+    /// Unix timestamp in seconds at which the subscription expires
+    pub expire: Option<i64>,
+    /// Suggested interval between profile updates, in hours
+    pub update_interval_hours: Option<u32>,
+    /// Suggested profile name, taken from `content-disposition`
+    pub profile_name: Option<String>,
+    /// The provider's page for the subscription
+    pub web_page_url: Option<String>,
+}
+
+impl SubscriptionInfo {
+    /// `None` when the response carries none of the headers
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+        };
+
+        let mut info = Self::default();
+        // upload=455727941; download=6174315083; total=1073741824000; expire=1671815872
+        if let Some(userinfo) = header("subscription-userinfo") {
+            for (key, value) in userinfo.split(';').filter_map(|kv| kv.split_once('=')) {
+                let value = parse_number(value);
+                match key.trim().to_ascii_lowercase().as_str() {
+                    "upload" => info.upload = value,
+                    "download" => info.download = value,
+                    "total" => info.total = value,
+                    // 0 is sent for subscriptions that never expire
+                    "expire" => info.expire = value.filter(|&v| v > 0).map(|v| v as i64),
+                    _ => {}
+                }
+            }
+        }
+        info.update_interval_hours =
+            header("profile-update-interval").and_then(|value| value.parse().ok());
+        info.profile_name = headers
+            .get(CONTENT_DISPOSITION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(disposition_filename);
+        info.web_page_url = header("profile-web-page-url").map(str::to_string);
+
+        (info != Self::default()).then_some(info)
+    }
+}
+
+/// Some providers send decimals or scientific notation for byte counts
+fn parse_number(value: &str) -> Option<u64> {
+    let value = value.trim();
+    value.parse::<u64>().ok().or_else(|| {
+        value
+            .parse::<f64>()
+            .ok()
+            .filter(|v| v.is_finite() && *v >= 0.0)
+            .map(|v| v as u64)
+    })
+}
+
+/// The file name from `attachment; filename*=UTF-8''...` or
+/// `attachment; filename="..."`, without a YAML extension
+fn disposition_filename(value: &str) -> Option<String> {
+    let mut plain = None;
+    let mut extended = None;
+    for param in value.split(';').skip(1) {
+        let Some((key, val)) = param.split_once('=') else {
+            continue;
+        };
+        match key.trim().to_ascii_lowercase().as_str() {
+            "filename*" => {
+                // RFC 5987: charset'language'percent-encoded-value
+                extended = val
+                    .trim()
+                    .splitn(3, '\'')
+                    .nth(2)
+                    .and_then(|encoded| urlencoding::decode(encoded).ok())
+                    .map(|name| name.into_owned());
+            }
+            "filename" => plain = Some(val.trim().trim_matches('"').to_string()),
+            _ => {}
+        }
+    }
+
+    let name = extended.or(plain)?;
+    let name = name
+        .strip_suffix(".yaml")
+        .or_else(|| name.strip_suffix(".yml"))
+        .unwrap_or(name.as_str())
+        .trim();
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::{HeaderName, HeaderValue};
+
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|&(name, value)| {
+                (
+                    HeaderName::from_static(name),
+                    HeaderValue::from_static(value),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parse_number_accepts_integers_decimals_and_exponents() {
+        let cases = [
+            ("455727941", Some(455727941)),
+            (" 6174315083 ", Some(6174315083)),
+            ("0", Some(0)),
+            ("1073741824000.00", Some(1073741824000)),
+            ("10737418240.9", Some(10737418240)),
+            ("1.073741824E12", Some(1073741824000)),
+            ("2e9", Some(2000000000)),
+            ("-1", None),
+            ("NaN", None),
+            ("inf", None),
+            ("", None),
+            ("unlimited", None),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(parse_number(value), expected, "{value:?}");
+        }
+    }
+
+    #[test]
+    fn disposition_filename_decodes_and_strips_yaml_extensions() {
+        let cases = [
+            ("attachment; filename=Clash", Some("Clash")),
+            (
+                "attachment; filename=\"Example Cloud.yaml\"",
+                Some("Example Cloud"),
+            ),
+            ("attachment;filename=config.yml", Some("config")),
+            ("attachment; FILENAME=\"nodes.txt\"", Some("nodes.txt")),
+            (
+                "attachment; filename*=UTF-8''%E6%B5%8B%E8%AF%95%E6%9C%BA%E5%9C%BA.yaml",
+                Some("测试机场"),
+            ),
+            (
+                "attachment; filename=\"fallback.yaml\"; filename*=UTF-8''%E9%A6%99%E6%B8%AF",
+                Some("香港"),
+            ),
+            (
+                "attachment; filename*=utf-8'en'My%20Sub.yml",
+                Some("My Sub"),
+            ),
+            ("attachment; filename=\".yaml\"", None),
+            ("attachment; filename=\"\"", None),
+            ("attachment", None),
+            ("inline", None),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(
+                disposition_filename(value).as_deref(),
+                expected,
+                "{value:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn from_headers_reads_provider_metadata() {
+        let info = SubscriptionInfo::from_headers(&headers(&[
+            (
+                "subscription-userinfo",
+                "upload=455727941; download=6174315083; total=1073741824000; expire=1671815872",
+            ),
+            ("profile-update-interval", "24"),
+            (
+                "content-disposition",
+                "attachment; filename*=UTF-8''%E6%B5%8B%E8%AF%95.yaml",
+            ),
+            ("profile-web-page-url", "https://example.com/user"),
+        ]));
+        assert_eq!(
+            info,
+            Some(SubscriptionInfo {
+                upload: Some(455727941),
+                download: Some(6174315083),
+                total: Some(1073741824000),
+                expire: Some(1671815872),
+                update_interval_hours: Some(24),
+                profile_name: Some("测试".to_string()),
+                web_page_url: Some("https://example.com/user".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn from_headers_handles_partial_and_odd_userinfo() {
+        let cases = [
+            (
+                "upload=0; download=1.5E9; total=1.073741824E11; expire=0",
+                SubscriptionInfo {
+                    upload: Some(0),
+                    download: Some(1500000000),
+                    total: Some(107374182400),
+                    ..Default::default()
+                },
+            ),
+            (
+                "Upload=1;Download=2;Total=3;Expire=4;",
+                SubscriptionInfo {
+                    upload: Some(1),
+                    download: Some(2),
+                    total: Some(3),
+                    expire: Some(4),
+                    ..Default::default()
+                },
+            ),
+            (
+                "upload=; download=abc; total=107374182400",
+                SubscriptionInfo {
+                    total: Some(107374182400),
+                    ..Default::default()
+                },
+            ),
+        ];
+        for (userinfo, expected) in cases {
+            let info =
+                SubscriptionInfo::from_headers(&headers(&[("subscription-userinfo", userinfo)]));
+            assert_eq!(info, Some(expected), "{userinfo:?}");
+        }
+    }
+
+    #[test]
+    fn from_headers_is_none_without_metadata() {
+        assert_eq!(SubscriptionInfo::from_headers(&HeaderMap::new()), None);
+        assert_eq!(
+            SubscriptionInfo::from_headers(&headers(&[
+                ("content-type", "text/yaml; charset=utf-8"),
+                ("subscription-userinfo", "  "),
+                ("profile-update-interval", "daily"),
+            ])),
+            None
+        );
+    }
+}
@@ -0,0 +1,442 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use ipnet::{Ipv4Net, Ipv6Net};
+
+use crate::{ProfileOverride, error::ClashError};
+
+/// Subnets home routers, hotspots and Wi-Fi Direct commonly hand out. A TUN
+/// address inside one of them would shadow hosts on the local network.
+const COMMON_LAN_SUBNETS: [(Ipv4Addr, u8); 7] = [
+    (Ipv4Addr::new(192, 168, 0, 0), 24),
+    (Ipv4Addr::new(192, 168, 1, 0), 24),
+    (Ipv4Addr::new(192, 168, 31, 0), 24), // Xiaomi routers
+    (Ipv4Addr::new(192, 168, 43, 0), 24), // Android hotspot
+    (Ipv4Addr::new(192, 168, 49, 0), 24), // Wi-Fi Direct
+    (Ipv4Addr::new(10, 0, 0, 0), 24),
+    (Ipv4Addr::new(10, 0, 1, 0), 24),
+];
+
+/// Smallest MTU every IPv6 link must support
+const MIN_MTU_V6: u16 = 1280;
+/// Smallest datagram every IPv4 host must accept
+const MIN_MTU_V4: u16 = 576;
+
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct IpPrefix {
+    pub address: String,
+    pub prefix_len: u8,
+}
+
+impl From<Ipv4Net> for IpPrefix {
+    fn from(net: Ipv4Net) -> Self {
+        Self {
+            address: net.addr().to_string(),
+            prefix_len: net.prefix_len(),
+        }
+    }
+}
+
+impl From<Ipv6Net> for IpPrefix {
+    fn from(net: Ipv6Net) -> Self {
+        Self {
+            address: net.addr().to_string(),
+            prefix_len: net.prefix_len(),
+        }
+    }
+}
+
+/// Everything `VpnService.Builder` needs to set up the interface the core
+/// expects, so the app does not repeat the addresses on its side
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct TunPlan {
+    /// Passed to `addAddress`
+    pub address_v4: IpPrefix,
+    /// Passed to `addAddress`; `None` when IPv6 is disabled
+    pub address_v6: Option<IpPrefix>,
+    /// Passed to `addRoute`
+    pub routes: Vec<IpPrefix>,
+    /// Passed to `addDnsServer`. Queries to them are answered by the core.
+    pub dns_servers: Vec<String>,
+    /// Passed to `setMtu`
+    pub mtu: u16,
+}
+
+/// Work out the TUN addressing for `over` and check it does not collide
+/// with the fake-ip range or a common LAN subnet
+#[uniffi::export]
+pub fn tun_plan(over: ProfileOverride) -> Result<TunPlan, ClashError> {
+    Ok(Addressing::new(&over)?.plan())
+}
+
+/// Parsed form of the TUN settings in `ProfileOverride`
+pub(crate) struct Addressing {
+    pub gateway: Ipv4Net,
+    pub gateway_v6: Option<Ipv6Net>,
+    /// `None` unless fake-ip is enabled
+    pub fake_ip_range: Option<Ipv4Net>,
+    pub mtu: u16,
+    pub routes_v4: Vec<Ipv4Net>,
+    pub routes_v6: Vec<Ipv6Net>,
+}
+
+impl Addressing {
+    pub(crate) fn new(over: &ProfileOverride) -> Result<Self, ClashError> {
+        let gateway: Ipv4Net = over.tun_gateway.parse().map_err(|e| {
+            ClashError::invalid_override(format!("tun_gateway {}: {e}", over.tun_gateway))
+        })?;
+        let gateway_v6 = over
+            .ipv6
+            .then(|| over.tun_gateway_v6.parse::<Ipv6Net>())
+            .transpose()
+            .map_err(|e| {
+                ClashError::invalid_override(format!("tun_gateway_v6 {}: {e}", over.tun_gateway_v6))
+            })?;
+        let fake_ip_range = over
+            .fake_ip
+            .then(|| over.fake_ip_range.parse::<Ipv4Net>())
+            .transpose()
+            .map_err(|e| {
+                ClashError::invalid_override(format!("fake_ip_range {}: {e}", over.fake_ip_range))
+            })?;
+        // The core's DNS server lives on the TUN subnets and fake-ip answers
+        // point into the fake-ip range, so no exclusion may route them around
+        // the tunnel
+        let (routes_v4, routes_v6) = match &over.route_exclusions {
+            Some(exclusions) => {
+                let keep_v4: Vec<_> = std::iter::once(gateway)
+                    .chain(fake_ip_range)
+                    .map(|net| net.trunc())
+                    .collect();
+                let keep_v6: Vec<_> = gateway_v6.map(|net| net.trunc()).into_iter().collect();
+                exclusions.included(&keep_v4, &keep_v6)?
+            }
+            None => (vec![Ipv4Net::default()], vec![Ipv6Net::default()]),
+        };
+        let addressing = Self {
+            gateway,
+            gateway_v6,
+            fake_ip_range,
+            mtu: over.tun_mtu,
+            routes_v4,
+            routes_v6,
+        };
+        addressing.validate()?;
+        Ok(addressing)
+    }
+
+    fn validate(&self) -> Result<(), ClashError> {
+        let gateway = self.gateway;
+        let is_host = |addr: Ipv4Addr| {
+            gateway.contains(&addr) && addr != gateway.network() && addr != gateway.broadcast()
+        };
+        if !is_host(gateway.addr()) || !is_host(dns_v4(gateway)) {
+            return Err(ClashError::invalid_override(format!(
+                "tun_gateway {gateway} must be a host address with a free host address after \
+                 it for the DNS server"
+            )));
+        }
+        if let Some((lan, len)) = COMMON_LAN_SUBNETS
+            .iter()
+            .find(|(addr, len)| overlaps(gateway, Ipv4Net::new(*addr, *len).unwrap()))
+        {
+            return Err(ClashError::invalid_override(format!(
+                "tun_gateway {gateway} overlaps the common LAN subnet {lan}/{len}"
+            )));
+        }
+        if let Some(fake_ip_range) = self.fake_ip_range.filter(|&r| overlaps(gateway, r)) {
+            return Err(ClashError::invalid_override(format!(
+                "tun_gateway {gateway} overlaps the fake-ip range {fake_ip_range}"
+            )));
+        }
+
+        if let Some(gateway_v6) = self.gateway_v6 {
+            // The subnet-router anycast address is the only reserved one in
+            // a prefix this small
+            if gateway_v6.addr() == gateway_v6.network()
+                || !gateway_v6.contains(&dns_v6(gateway_v6))
+            {
+                return Err(ClashError::invalid_override(format!(
+                    "tun_gateway_v6 {gateway_v6} must be a host address with a free host \
+                     address after it for the DNS server"
+                )));
+            }
+            if !is_global_or_ula(gateway_v6.addr()) {
+                return Err(ClashError::invalid_override(format!(
+                    "tun_gateway_v6 {gateway_v6} must be a unique local or global address"
+                )));
+            }
+        }
+
+        let min_mtu = if self.gateway_v6.is_some() {
+            MIN_MTU_V6
+        } else {
+            MIN_MTU_V4
+        };
+        if self.mtu < min_mtu {
+            return Err(ClashError::invalid_override(format!(
+                "tun_mtu {} is below the minimum of {min_mtu}",
+                self.mtu
+            )));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn plan(&self) -> TunPlan {
+        let mut routes: Vec<IpPrefix> =
+            self.routes_v4.iter().copied().map(IpPrefix::from).collect();
+        let mut dns_servers = vec![dns_v4(self.gateway).to_string()];
+        if let Some(gateway_v6) = self.gateway_v6 {
+            routes.extend(self.routes_v6.iter().copied().map(IpPrefix::from));
+            dns_servers.push(dns_v6(gateway_v6).to_string());
+        }
+        TunPlan {
+            address_v4: self.gateway.into(),
+            address_v6: self.gateway_v6.map(Into::into),
+            routes,
+            dns_servers,
+            mtu: self.mtu,
+        }
+    }
+}
+
+/// The DNS server sits on the host address right after the gateway
+fn dns_v4(gateway: Ipv4Net) -> Ipv4Addr {
+    Ipv4Addr::from(u32::from(gateway.addr()).wrapping_add(1))
+}
+
+fn dns_v6(gateway: Ipv6Net) -> Ipv6Addr {
+    Ipv6Addr::from(u128::from(gateway.addr()).wrapping_add(1))
+}
+
+fn overlaps(a: Ipv4Net, b: Ipv4Net) -> bool {
+    a.contains(&b.network()) || b.contains(&a.network())
+}
+
+fn is_global_or_ula(addr: Ipv6Addr) -> bool {
+    let first = addr.segments()[0];
+    // fc00::/7 unique local, 2000::/3 global unicast
+    first & 0xfe00 == 0xfc00 || first & 0xe000 == 0x2000
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use ipnet::IpNet;
+
+    use super::*;
+    use crate::route::RouteExclusions;
+
+    /// `ProfileOverride` with the defaults the bindings fill in
+    fn over() -> ProfileOverride {
+        ProfileOverride {
+            tun_fd: -1,
+            allow_lan: false,
+            mixed_port: 7890,
+            http_port: None,
+            socks_port: None,
+            fake_ip: false,
+            fake_ip_range: "198.18.0.2/16".to_string(),
+            ipv6: true,
+            tun_gateway: "172.19.0.1/30".to_string(),
+            tun_gateway_v6: "fdfe:dcba:9876::1/126".to_string(),
+            tun_mtu: 1500,
+            route_exclusions: None,
+            nameserver: Vec::new(),
+            default_nameserver: Vec::new(),
+            fallback: Vec::new(),
+            dns_preset: None,
+        }
+    }
+
+    fn prefix(address: &str, prefix_len: u8) -> IpPrefix {
+        IpPrefix {
+            address: address.to_string(),
+            prefix_len,
+        }
+    }
+
+    /// How many of the plan's routes cover `addr`
+    fn routes_covering(plan: &TunPlan, addr: &str) -> usize {
+        let addr: IpAddr = addr.parse().unwrap();
+        plan.routes
+            .iter()
+            .filter(|route| {
+                format!("{}/{}", route.address, route.prefix_len)
+                    .parse::<IpNet>()
+                    .unwrap()
+                    .contains(&addr)
+            })
+            .count()
+    }
+
+    fn assert_invalid(over: ProfileOverride) {
+        assert!(
+            matches!(tun_plan(over), Err(ClashError::InvalidOverride { .. })),
+            "expected InvalidOverride"
+        );
+    }
+
+    #[test]
+    fn default_plan() {
+        assert_eq!(
+            tun_plan(over()).unwrap(),
+            TunPlan {
+                address_v4: prefix("172.19.0.1", 30),
+                address_v6: Some(prefix("fdfe:dcba:9876::1", 126)),
+                routes: vec![prefix("0.0.0.0", 0), prefix("::", 0)],
+                dns_servers: vec!["172.19.0.2".to_string(), "fdfe:dcba:9876::2".to_string()],
+                mtu: 1500,
+            }
+        );
+    }
+
+    #[test]
+    fn ipv6_disabled_leaves_out_the_v6_side() {
+        let plan = tun_plan(ProfileOverride {
+            ipv6: false,
+            tun_gateway_v6: "not an address".to_string(),
+            ..over()
+        })
+        .unwrap();
+        assert_eq!(plan.address_v6, None);
+        assert_eq!(plan.routes, vec![prefix("0.0.0.0", 0)]);
+        assert_eq!(plan.dns_servers, vec!["172.19.0.2".to_string()]);
+    }
+
+    #[test]
+    fn exclusions_keep_the_tun_subnets_and_fake_ip_range_routed() {
+        let plan = tun_plan(ProfileOverride {
+            fake_ip: true,
+            route_exclusions: Some(RouteExclusions {
+                private: true,
+                cidrs: vec!["198.18.0.0/15".to_string(), "fd00::/8".to_string()],
+                direct_rules_profile: None,
+            }),
+            ..over()
+        })
+        .unwrap();
+        for addr in [
+            "172.19.0.1",
+            "172.19.0.2",
+            "198.18.0.1",
+            "198.18.255.255",
+            "8.8.8.8",
+            "fdfe:dcba:9876::2",
+            "2001:4860:4860::8888",
+        ] {
+            assert_eq!(routes_covering(&plan, addr), 1, "{addr} should be routed");
+        }
+        for addr in [
+            "172.19.0.4",
+            "172.16.0.1",
+            "192.168.1.1",
+            "198.19.0.1",
+            "fd00::1",
+            "fdfe:dcba:9876::4",
+            "fe80::1",
+        ] {
+            assert_eq!(routes_covering(&plan, addr), 0, "{addr} should be excluded");
+        }
+    }
+
+    #[test]
+    fn gateway_needs_a_host_address_after_it() {
+        for gateway in [
+            "172.19.0.0/30",  // network address
+            "172.19.0.2/30",  // DNS would be the broadcast address
+            "172.19.0.3/30",  // broadcast address, DNS outside the subnet
+            "172.19.0.1/31",  // no host addresses at all
+            "172.19.0.1/32",  // single address
+            "not an address", // unparsable
+        ] {
+            assert_invalid(ProfileOverride {
+                tun_gateway: gateway.to_string(),
+                ..over()
+            });
+        }
+        let plan = tun_plan(ProfileOverride {
+            tun_gateway: "172.19.0.5/29".to_string(),
+            ..over()
+        })
+        .unwrap();
+        assert_eq!(plan.dns_servers[0], "172.19.0.6");
+    }
+
+    #[test]
+    fn gateway_v6_needs_a_host_address_after_it() {
+        for gateway in [
+            "fdfe:dcba:9876::/126",  // subnet-router anycast
+            "fdfe:dcba:9876::3/126", // DNS outside the subnet
+            "fdfe:dcba:9876::1/128", // no room at all
+            "fe80::1/64",            // link-local
+            "ff02::1/64",            // multicast
+        ] {
+            assert_invalid(ProfileOverride {
+                tun_gateway_v6: gateway.to_string(),
+                ..over()
+            });
+        }
+    }
+
+    #[test]
+    fn gateway_must_not_overlap_a_common_lan() {
+        for gateway in [
+            "192.168.1.1/30",
+            "10.0.0.1/30",
+            "10.0.0.1/16",
+            "192.168.0.1/16",
+        ] {
+            assert_invalid(ProfileOverride {
+                tun_gateway: gateway.to_string(),
+                ..over()
+            });
+        }
+    }
+
+    #[test]
+    fn gateway_must_not_overlap_the_fake_ip_range() {
+        let overlapping = ProfileOverride {
+            tun_gateway: "198.18.0.1/30".to_string(),
+            ..over()
+        };
+        assert!(tun_plan(overlapping.clone()).is_ok());
+        assert_invalid(ProfileOverride {
+            fake_ip: true,
+            ..overlapping.clone()
+        });
+        assert_invalid(ProfileOverride {
+            fake_ip: true,
+            tun_gateway: "198.0.0.1/8".to_string(),
+            ..overlapping
+        });
+    }
+
+    #[test]
+    fn mtu_minimum_depends_on_ipv6() {
+        assert_invalid(ProfileOverride {
+            tun_mtu: 1279,
+            ..over()
+        });
+        assert!(
+            tun_plan(ProfileOverride {
+                tun_mtu: 1280,
+                ..over()
+            })
+            .is_ok()
+        );
+        assert!(
+            tun_plan(ProfileOverride {
+                ipv6: false,
+                tun_mtu: 576,
+                ..over()
+            })
+            .is_ok()
+        );
+        assert_invalid(ProfileOverride {
+            ipv6: false,
+            tun_mtu: 575,
+            ..over()
+        });
+    }
+}
@@ -1,17 +1,156 @@
-use eyre::Context;
+use std::{
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use reqwest::{
+    StatusCode,
+    header::{
+        CONTENT_RANGE, ETAG, HeaderMap, HeaderName, HeaderValue, IF_MODIFIED_SINCE, IF_NONE_MATCH,
+        IF_RANGE, LAST_MODIFIED, RANGE,
+    },
+    redirect::Policy,
+};
+use tempfile::NamedTempFile;
+use tokio::{
+    io::{AsyncSeekExt, AsyncWriteExt},
+    sync::watch,
+};
 use tokio_stream::StreamExt;
-use tracing::{error, info};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+use url::Url;
+
+use crate::{error::ClashError, subscription::SubscriptionInfo};
 
-use crate::EyreError;
+/// Shortest gap between two progress callbacks
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+/// Longest part of an error response kept in [`ClashError::HttpStatus`]
+const ERROR_BODY_CHARS: usize = 512;
+const MAX_REDIRECTS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum DownloadOutcome {
+    /// A new copy was written to `output_path`
+    Downloaded,
+    /// The server reported that the copy at `output_path` is current. The
+    /// file was left as it is.
+    NotModified,
+}
 
-#[derive(uniffi::Record)]
+#[derive(Clone, uniffi::Record)]
 pub struct DownloadResult {
-    pub success: bool,
+    pub outcome: DownloadOutcome,
     pub file_size: u64,
-    pub error_message: Option<String>,
+    /// Quota, expiry and naming hints from the provider's response headers
+    pub subscription: Option<SubscriptionInfo>,
+    /// Validators to pass back in [`DownloadOptions`] on the next download
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+#[derive(Clone, uniffi::Record)]
+pub struct HttpHeader {
+    pub name: String,
+    pub value: String,
+}
+
+/// Authentication sent with every request of a download
+#[derive(Clone, uniffi::Enum)]
+pub enum Credentials {
+    /// `Authorization: Basic ...`
+    Basic {
+        username: String,
+        password: Option<String>,
+    },
+    /// `Authorization: Bearer ...`
+    Bearer { token: String },
+}
+
+#[derive(Clone, uniffi::Record)]
+pub struct DownloadOptions {
+    /// `etag` of the previous download, sent as `If-None-Match`
+    #[uniffi(default = None)]
+    pub etag: Option<String>,
+    /// `last_modified` of the previous download, sent as `If-Modified-Since`
+    #[uniffi(default = None)]
+    pub last_modified: Option<String>,
+    /// Parse the download as a profile before it replaces `output_path`.
+    /// Turn this off for rule sets and geo databases.
+    #[uniffi(default = true)]
+    pub verify_config: bool,
+    /// Further attempts after a dropped connection or a 429/5xx response.
+    /// A dropped transfer resumes with a `Range` request when the server
+    /// supports it.
+    #[uniffi(default = 3)]
+    pub max_retries: u32,
+    /// Wait before the first retry, doubled for each one after it
+    #[uniffi(default = 1000)]
+    pub retry_backoff_ms: u64,
+    #[uniffi(default = 30000)]
+    pub max_retry_backoff_ms: u64,
+    /// Extra request headers, such as `X-Token`. Their values are never
+    /// logged, and redirects to another origin are refused while any are
+    /// set.
+    #[uniffi(default = [])]
+    pub headers: Vec<HttpHeader>,
+    #[uniffi(default = None)]
+    pub credentials: Option<Credentials>,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            etag: None,
+            last_modified: None,
+            verify_config: true,
+            max_retries: 3,
+            retry_backoff_ms: 1000,
+            max_retry_backoff_ms: 30000,
+            headers: Vec::new(),
+            credentials: None,
+        }
+    }
 }
 
-#[derive(uniffi::Record)]
+impl DownloadOptions {
+    fn header_map(&self) -> Result<HeaderMap, ClashError> {
+        let mut headers = HeaderMap::new();
+        for header in &self.headers {
+            let name = HeaderName::try_from(header.name.trim()).map_err(|e| {
+                ClashError::invalid_override(format!("header name {}: {e}", header.name))
+            })?;
+            let mut value = HeaderValue::try_from(header.value.as_str()).map_err(|e| {
+                ClashError::invalid_override(format!("value of header {}: {e}", header.name))
+            })?;
+            value.set_sensitive(true);
+            headers.append(name, value);
+        }
+        Ok(headers)
+    }
+
+    fn authenticate(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.credentials {
+            Some(Credentials::Basic { username, password }) => {
+                request.basic_auth(username, password.as_deref())
+            }
+            Some(Credentials::Bearer { token }) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        let ms = self
+            .retry_backoff_ms
+            .saturating_mul(1 << attempt.min(16))
+            .min(self.max_retry_backoff_ms);
+        Duration::from_millis(ms)
+    }
+}
+
+#[derive(Debug, Clone, Default, uniffi::Record)]
 pub struct DownloadProgress {
     pub downloaded: u64,
     pub total: u64,
@@ -22,123 +161,500 @@ pub trait DownloadProgressCallback: Send + Sync {
     fn on_progress(&self, progress: DownloadProgress);
 }
 
+/// Handle to a download started with [`start_download`]. Dropping it does
+/// not stop the download.
+#[derive(uniffi::Object)]
+pub struct DownloadTask {
+    cancel_token: CancellationToken,
+    progress: Arc<Mutex<DownloadProgress>>,
+    result: watch::Receiver<Option<Result<DownloadResult, ClashError>>>,
+}
+
 #[uniffi::export(async_runtime = "tokio")]
+impl DownloadTask {
+    /// Stop the download and remove its temp file. `output_path` is left as
+    /// it was and `await_result` fails with `ClashError::Cancelled`.
+    pub fn cancel(&self) {
+        self.cancel_token.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel_token.is_cancelled()
+    }
+
+    /// Latest progress, updated on every chunk regardless of how often the
+    /// progress callback fires
+    pub fn progress(&self) -> DownloadProgress {
+        self.progress.lock().unwrap().clone()
+    }
+
+    /// Wait for the download to finish. May be called any number of times.
+    pub async fn await_result(&self) -> Result<DownloadResult, ClashError> {
+        let mut result = self.result.clone();
+        let result = result
+            .wait_for(Option::is_some)
+            .await
+            .map_err(|_| ClashError::other("Download task ended without a result"))?;
+        result.as_ref().unwrap().clone()
+    }
+}
+
+/// Start [`download_file_with_progress`] in the background and return a
+/// handle to follow or cancel it
+#[uniffi::export(async_runtime = "tokio", default(options = None))]
+pub async fn start_download(
+    url: String,
+    output_path: String,
+    user_agent: Option<String>,
+    proxy_url: Option<String>,
+    progress_callback: Option<Box<dyn DownloadProgressCallback>>,
+    options: Option<DownloadOptions>,
+) -> Arc<DownloadTask> {
+    let progress = Progress::new(progress_callback);
+    let snapshot = progress.snapshot.clone();
+    let cancel_token = CancellationToken::new();
+    let token = cancel_token.clone();
+    let (result_tx, result_rx) = watch::channel(None);
+    tokio::spawn(async move {
+        let options = options.unwrap_or_default();
+        let result = tokio::select! {
+            result = download(url, output_path, user_agent, proxy_url, progress, options) => result,
+            _ = token.cancelled() => {
+                info!("Download cancelled");
+                Err(ClashError::Cancelled)
+            }
+        };
+        _ = result_tx.send(Some(result));
+    });
+    Arc::new(DownloadTask {
+        cancel_token,
+        progress: snapshot,
+        result: result_rx,
+    })
+}
+
+#[uniffi::export(async_runtime = "tokio", default(options = None))]
 pub async fn download_file(
     url: String,
     output_path: String,
     user_agent: Option<String>,
     proxy_url: Option<String>,
-) -> Result<DownloadResult, EyreError> {
-    download_file_with_progress(url, output_path, user_agent, proxy_url, None).await
+    options: Option<DownloadOptions>,
+) -> Result<DownloadResult, ClashError> {
+    download_file_with_progress(url, output_path, user_agent, proxy_url, None, options).await
 }
 
-#[uniffi::export(async_runtime = "tokio")]
+/// Download a profile to `output_path`. The body is streamed to a temp file
+/// in the same directory and only renamed over `output_path` once it is
+/// complete and, unless `options` turn `verify_config` off, parses as a
+/// config, so a failed or invalid download leaves the old file intact.
+#[uniffi::export(async_runtime = "tokio", default(options = None))]
 pub async fn download_file_with_progress(
     url: String,
     output_path: String,
     user_agent: Option<String>,
     proxy_url: Option<String>,
     progress_callback: Option<Box<dyn DownloadProgressCallback>>,
-) -> Result<DownloadResult, EyreError> {
-    info!("Starting download from: {}", url);
+    options: Option<DownloadOptions>,
+) -> Result<DownloadResult, ClashError> {
+    let progress = Progress::new(progress_callback);
+    download(
+        url,
+        output_path,
+        user_agent,
+        proxy_url,
+        progress,
+        options.unwrap_or_default(),
+    )
+    .await
+}
+
+async fn download(
+    url: String,
+    output_path: String,
+    user_agent: Option<String>,
+    proxy_url: Option<String>,
+    mut progress: Progress,
+    options: DownloadOptions,
+) -> Result<DownloadResult, ClashError> {
+    let log_url = redact_url(&url);
+    info!("Starting download from: {}", log_url);
 
     let ua = user_agent.unwrap_or_else(|| "clash-android/1.0".to_string());
     info!("Using User-Agent: {}", ua);
 
+    let headers = options.header_map()?;
+    if !headers.is_empty() {
+        let names: Vec<&str> = headers.keys().map(HeaderName::as_str).collect();
+        info!("Using custom headers: {}", names.join(", "));
+    }
+
     // Build reqwest client.
     // reqwest v0.12.28 with rustls-tls-webpki-roots uses bundled Mozilla CA
     // certificates and does NOT depend on rustls-platform-verifier.
     let mut client_builder = reqwest::Client::builder()
         .user_agent(&ua)
-        .redirect(reqwest::redirect::Policy::limited(10));
+        .redirect(redirect_policy(!headers.is_empty()));
 
     // Add proxy if provided
     if let Some(proxy) = proxy_url {
-        info!("Using proxy: {}", proxy);
-        let proxy =
-            reqwest::Proxy::all(&proxy).map_err(|e| eyre::eyre!("Invalid proxy URL: {}", e))?;
+        info!("Using proxy: {}", redact_url(&proxy));
+        let proxy = reqwest::Proxy::all(&proxy).map_err(|e| {
+            ClashError::invalid_override(format!("Invalid proxy URL: {}", e.without_url()))
+        })?;
         client_builder = client_builder.proxy(proxy);
     }
 
     let client = client_builder
         .build()
-        .map_err(|e| eyre::eyre!("Failed to build HTTP client: {}", e))?;
+        .map_err(|e| ClashError::network(format!("Failed to build HTTP client: {e}")))?;
+
+    let request = DownloadRequest {
+        client,
+        url: &url,
+        headers,
+        options: &options,
+    };
 
-    // Send request
-    info!("Sending request to: {}", url);
-    let response = client
-        .get(&url)
-        .send()
+    // A 304 is only useful while there is a copy to keep
+    let existing = tokio::fs::metadata(&output_path)
         .await
-        .map_err(|e| eyre::eyre!("Failed to send request: {}", e))?;
-
-    let status = response.status();
-    if !status.is_success() {
-        error!(
-            "HTTP request failed with status: {} for URL: {}",
-            status, url
-        );
-        return Ok(DownloadResult {
-            success: false,
-            file_size: 0,
-            error_message: Some(format!(
-                "HTTP {} - {}",
-                status.as_u16(),
-                status.canonical_reason().unwrap_or("Unknown")
-            )),
-        });
-    }
-
-    // Get content length
-    let total_size = response.content_length().unwrap_or(0);
-    info!("Content length: {} bytes", total_size);
-
-    // Report initial progress
-    if let Some(ref callback) = progress_callback {
-        info!("Reporting initial progress: 0/{}", total_size);
-        callback.on_progress(DownloadProgress {
-            downloaded: 0,
-            total: total_size,
-        });
-    }
-
-    // Download with progress tracking
-    let mut stream = response.bytes_stream();
-    let mut downloaded: u64 = 0;
-    let mut buffer = Vec::new();
-
-    while let Some(chunk) = stream.next().await {
-        let chunk = chunk.map_err(|e| eyre::eyre!("Failed to read chunk: {}", e))?;
-        buffer.extend_from_slice(&chunk);
-        downloaded += chunk.len() as u64;
-
-        // Report progress
-        if let Some(ref callback) = progress_callback {
-            info!("Progress: {}/{} bytes", downloaded, total_size);
-            callback.on_progress(DownloadProgress {
-                downloaded,
-                total: total_size,
-            });
+        .ok()
+        .filter(|metadata| metadata.is_file());
+
+    // Stream into a temp file next to the target, so the rename below stays
+    // on one filesystem and the old file is untouched until it succeeds.
+    // The temp file is removed on every early return.
+    let dir = match Path::new(&output_path).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let (file, temp_path) = NamedTempFile::new_in(&dir)
+        .map_err(|e| {
+            ClashError::io(format!(
+                "Failed to create temp file in {}: {e}",
+                dir.display()
+            ))
+        })?
+        .into_parts();
+    let mut file = tokio::fs::File::from_std(file);
+
+    let mut transfer = Transfer::default();
+    let mut attempt = 0;
+    loop {
+        let fetched = transfer
+            .fetch(&request, existing.is_some(), &mut file, &mut progress)
+            .await;
+        match fetched {
+            Ok(Fetched::Complete) => break,
+            Ok(Fetched::NotModified) => {
+                info!("{} is up to date", output_path);
+                return Ok(DownloadResult {
+                    outcome: DownloadOutcome::NotModified,
+                    file_size: existing.map(|metadata| metadata.len()).unwrap_or_default(),
+                    subscription: transfer.subscription,
+                    etag: transfer.etag.or(options.etag),
+                    last_modified: transfer.last_modified.or(options.last_modified),
+                });
+            }
+            Err(e) if attempt < options.max_retries && is_retryable(&e) => {
+                let backoff = options.backoff(attempt);
+                attempt += 1;
+                warn!(
+                    "Download attempt {} failed, retrying in {:?}: {}",
+                    attempt, backoff, e
+                );
+                tokio::time::sleep(backoff).await;
+            }
+            Err(e) => {
+                if let ClashError::HttpStatus { code, .. } = &e {
+                    error!(
+                        "HTTP request failed with status: {} for URL: {}",
+                        code, log_url
+                    );
+                }
+                return Err(e);
+            }
         }
     }
-
-    // Create output file and write
-    _ = tokio::fs::File::create(&output_path)
-        .await
-        .context(format!("Failed to create file: {output_path}"))?;
-    tokio::fs::write(&output_path, &buffer)
+    file.sync_all()
         .await
-        .context(format!("Failed to write to file: {output_path}"))?;
+        .map_err(|e| ClashError::io(format!("Failed to flush temp file: {e}")))?;
+    drop(file);
+
+    // Reject anything the core could not load, before it replaces a
+    // working profile
+    if options.verify_config {
+        let verify_path = temp_path.to_string_lossy().into_owned();
+        tokio::task::spawn_blocking(move || crate::verify_config(&verify_path))
+            .await
+            .map_err(ClashError::other)??;
+    }
 
-    let file_size = buffer.len() as u64;
+    temp_path
+        .persist(&output_path)
+        .map_err(|e| ClashError::io(format!("Failed to replace {output_path}: {e}")))?;
+
+    let file_size = transfer.downloaded;
     info!(
         "Download completed: {} bytes written to {}",
         file_size, output_path
     );
 
     Ok(DownloadResult {
-        success: true,
+        outcome: DownloadOutcome::Downloaded,
         file_size,
-        error_message: None,
+        subscription: transfer.subscription,
+        etag: transfer.etag,
+        last_modified: transfer.last_modified,
     })
 }
+
+/// Keeps the snapshot behind [`DownloadTask::progress`] current and forwards
+/// it to the app's callback at most once per [`PROGRESS_INTERVAL`]
+struct Progress {
+    snapshot: Arc<Mutex<DownloadProgress>>,
+    callback: Option<Box<dyn DownloadProgressCallback>>,
+    last_report: Option<Instant>,
+}
+
+impl Progress {
+    fn new(callback: Option<Box<dyn DownloadProgressCallback>>) -> Self {
+        Self {
+            snapshot: Arc::default(),
+            callback,
+            last_report: None,
+        }
+    }
+
+    /// `force` reports regardless of the interval, for the first and last
+    /// update of a transfer
+    fn update(&mut self, downloaded: u64, total: u64, force: bool) {
+        let progress = DownloadProgress { downloaded, total };
+        *self.snapshot.lock().unwrap() = progress.clone();
+
+        let due = self
+            .last_report
+            .is_none_or(|last| last.elapsed() >= PROGRESS_INTERVAL);
+        if !force && !due {
+            return;
+        }
+        self.last_report = Some(Instant::now());
+        debug!("Progress: {}/{} bytes", downloaded, total);
+        if let Some(callback) = &self.callback {
+            callback.on_progress(progress);
+        }
+    }
+}
+
+enum Fetched {
+    Complete,
+    NotModified,
+}
+
+/// What every attempt of a download sends
+struct DownloadRequest<'a> {
+    client: reqwest::Client,
+    url: &'a str,
+    headers: HeaderMap,
+    options: &'a DownloadOptions,
+}
+
+impl DownloadRequest<'_> {
+    /// A GET with the custom headers and credentials applied
+    fn get(&self) -> reqwest::RequestBuilder {
+        let request = self.client.get(self.url).headers(self.headers.clone());
+        self.options.authenticate(request)
+    }
+}
+
+/// State of one download, carried across retries so a dropped transfer can
+/// pick up where it stopped
+#[derive(Default)]
+struct Transfer {
+    downloaded: u64,
+    total: u64,
+    /// Strong `ETag` or `Last-Modified` of the body being downloaded, sent as
+    /// `If-Range` so a resumed request never mixes two versions
+    validator: Option<String>,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    subscription: Option<SubscriptionInfo>,
+}
+
+impl Transfer {
+    /// Make one request and stream its body into `file`
+    async fn fetch(
+        &mut self,
+        download: &DownloadRequest<'_>,
+        conditional: bool,
+        file: &mut tokio::fs::File,
+        progress: &mut Progress,
+    ) -> Result<Fetched, ClashError> {
+        let options = download.options;
+        let resume = self.downloaded > 0 && self.validator.is_some();
+        let mut request = download.get();
+        if let Some(validator) = self.validator.as_deref().filter(|_| resume) {
+            info!("Resuming download at {} bytes", self.downloaded);
+            request = request
+                .header(RANGE, format!("bytes={}-", self.downloaded))
+                .header(IF_RANGE, validator);
+        } else if conditional {
+            if let Some(etag) = &options.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &options.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        info!("Sending request to: {}", redact_url(download.url));
+        let response = request.send().await.map_err(|e| {
+            ClashError::network(format!("Failed to send request: {}", e.without_url()))
+        })?;
+
+        let status = response.status();
+        if status == StatusCode::NOT_MODIFIED && !resume {
+            // Providers send the quota and expiry on every response, so they
+            // are current even when the profile is not
+            self.read_validators(response.headers());
+            self.subscription = SubscriptionInfo::from_headers(response.headers());
+            return Ok(Fetched::NotModified);
+        }
+        if !status.is_success() {
+            // Error pages can be large, the start is enough to tell what
+            // went wrong
+            let body = response.text().await.unwrap_or_default();
+            return Err(ClashError::HttpStatus {
+                code: status.as_u16(),
+                body: body.chars().take(ERROR_BODY_CHARS).collect(),
+            });
+        }
+
+        let range = (status == StatusCode::PARTIAL_CONTENT)
+            .then(|| content_range(response.headers()))
+            .flatten();
+        match range {
+            Some((start, total)) if resume && start == self.downloaded => {
+                self.total =
+                    total.unwrap_or(self.downloaded + response.content_length().unwrap_or(0));
+            }
+            _ => {
+                // A fresh body, either the first one or because the server
+                // ignored the range or the file changed since
+                if self.downloaded > 0 {
+                    file.set_len(0).await.map_err(|e| {
+                        ClashError::io(format!("Failed to truncate temp file: {e}"))
+                    })?;
+                    file.seek(SeekFrom::Start(0))
+                        .await
+                        .map_err(|e| ClashError::io(format!("Failed to rewind temp file: {e}")))?;
+                    self.downloaded = 0;
+                }
+                if status == StatusCode::PARTIAL_CONTENT {
+                    // A range we did not ask for; start over without one
+                    self.validator = None;
+                    return Err(ClashError::network("Unexpected partial response"));
+                }
+                self.read_validators(response.headers());
+                self.subscription = SubscriptionInfo::from_headers(response.headers());
+                self.total = response.content_length().unwrap_or(0);
+                info!("Content length: {} bytes", self.total);
+            }
+        }
+
+        progress.update(self.downloaded, self.total, true);
+
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|e| {
+                ClashError::network(format!("Failed to read chunk: {}", e.without_url()))
+            })?;
+            file.write_all(&chunk)
+                .await
+                .map_err(|e| ClashError::io(format!("Failed to write temp file: {e}")))?;
+            self.downloaded += chunk.len() as u64;
+            progress.update(self.downloaded, self.total, false);
+        }
+        progress.update(self.downloaded, self.total, true);
+        Ok(Fetched::Complete)
+    }
+
+    fn read_validators(&mut self, headers: &HeaderMap) {
+        let header = |name: HeaderName| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        self.etag = header(ETAG);
+        self.last_modified = header(LAST_MODIFIED);
+        // Weak ETags must not be used for range requests (RFC 9110)
+        self.validator = self
+            .etag
+            .clone()
+            .filter(|etag| !etag.starts_with("W/"))
+            .or_else(|| self.last_modified.clone());
+    }
+}
+
+/// Connection failures and server-side errors may go away on their own
+fn is_retryable(err: &ClashError) -> bool {
+    match err {
+        ClashError::Network { .. } => true,
+        ClashError::HttpStatus { code, .. } => *code == 429 || *code >= 500,
+        _ => false,
+    }
+}
+
+/// Start offset and total length from `Content-Range: bytes 100-999/1000`
+fn content_range(headers: &HeaderMap) -> Option<(u64, Option<u64>)> {
+    let value = headers.get(CONTENT_RANGE)?.to_str().ok()?;
+    let (range, total) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let (start, _) = range.split_once('-')?;
+    Some((start.trim().parse().ok()?, total.trim().parse().ok()))
+}
+
+/// Custom headers go out on every hop, and reqwest only strips
+/// `Authorization` and `Cookie` when a redirect leaves the host. With custom
+/// headers set, redirects to another origin are refused instead of handing
+/// the headers to a host the user never named.
+fn redirect_policy(same_origin_only: bool) -> Policy {
+    if !same_origin_only {
+        return Policy::limited(MAX_REDIRECTS);
+    }
+    Policy::custom(|attempt| {
+        let same_origin = attempt
+            .previous()
+            .first()
+            .is_none_or(|first| first.origin() == attempt.url().origin());
+        if attempt.previous().len() > MAX_REDIRECTS {
+            attempt.error("too many redirects")
+        } else if !same_origin {
+            let target = redact_url(attempt.url().as_str());
+            attempt.error(format!(
+                "refusing to follow a redirect to {target} with custom headers set"
+            ))
+        } else {
+            attempt.follow()
+        }
+    })
+}
+
+/// Subscription URLs carry their token in the userinfo, path or query, so
+/// only the scheme, host and port are logged
+fn redact_url(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(url) => {
+            let mut redacted = format!("{}://{}", url.scheme(), url.host_str().unwrap_or(""));
+            if let Some(port) = url.port() {
+                redacted.push_str(&format!(":{port}"));
+            }
+            if url.path() != "/" || url.query().is_some() || !url.username().is_empty() {
+                redacted.push_str("/<redacted>");
+            }
+            redacted
+        }
+        Err(_) => "<invalid URL>".to_string(),
+    }
+}